@@ -1,12 +1,20 @@
 use anyhow::{bail, Context, Result};
-use clap::{ArgAction, CommandFactory, FromArgMatches};
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser};
 use lexopt::Arg;
+use std::collections::HashSet;
 use std::env;
 use std::ffi::OsString;
+use std::fmt;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::str::FromStr;
-use wasmparser::Payload;
+use std::time::{Duration, Instant};
+use wasm_encoder::RawSection;
+use wasmparser::{Payload, Validator, WasmFeatures};
+use wit_component::StringEncoding;
+use wit_parser::{PackageId, Resolve, UnresolvedPackageGroup, WorldId};
 
 /// Representation of a flag passed to `wasm-ld`
 ///
@@ -16,8 +24,8 @@ use wasmparser::Payload;
 /// support optional values as `--foo=bar` but can't be specified as
 /// `--foo bar`.
 ///
-/// Finally there's currently only one "weird" flag which is `-shared` which has
-/// a single dash but a long name. That's specially handled elsewhere.
+/// Finally there are a few "weird" flags such as `-shared` and `-mllvm` which
+/// have a single dash but a long name. Those are specially handled elsewhere.
 ///
 /// The general goal here is that we want to inherit `wasm-ld`'s CLI but also
 /// want to be able to reserve CLI flags for this linker itself, so `wasm-ld`'s
@@ -211,13 +219,86 @@ const LLD_FLAGS: &[LldFlag] = &[
     flag! { -z OPT },
 ];
 
-const LLD_LONG_FLAGS_NONSTANDARD: &[&str] = &["-shared"];
+/// Flags with a single dash but a long name, which `lexopt` would otherwise
+/// parse as a cluster of short flags. The boolean indicates whether the flag
+/// takes a value, either as `-flag=value` or as `-flag value`.
+const LLD_LONG_FLAGS_NONSTANDARD: &[(&str, bool)] = &[("-shared", false), ("-mllvm", true)];
+
+/// The component model binary format version emitted by `wit-component`.
+const COMPONENT_FORMAT_VERSION: u16 = 0xd;
+
+/// The interfaces imported by the `wasi:http/proxy` world, without versions.
+const PROXY_WORLD_IMPORTS: &[&str] = &[
+    "wasi:cli/stderr",
+    "wasi:cli/stdin",
+    "wasi:cli/stdout",
+    "wasi:clocks/monotonic-clock",
+    "wasi:clocks/wall-clock",
+    "wasi:http/outgoing-handler",
+    "wasi:http/types",
+    "wasi:io/error",
+    "wasi:io/poll",
+    "wasi:io/streams",
+    "wasi:random/random",
+];
 
 #[derive(Default)]
 struct App {
     component: ComponentLdArgs,
+    invocation: Vec<OsString>,
     lld_args: Vec<OsString>,
+    objects: Vec<OsString>,
+    wit_files: Vec<PathBuf>,
     shared: bool,
+    post_encode: Option<PostEncodeHook>,
+    toolchain_linker: Option<PathBuf>,
+    temp_dir: Option<tempfile::TempDir>,
+}
+
+type PostEncodeHook = Box<dyn FnMut(&mut Vec<u8>) -> Result<()>>;
+
+/// Programmatic interface to `wasm-component-ld`.
+///
+/// This accepts the same arguments as the command line and additionally
+/// supports hooks which can't be expressed as flags.
+pub struct Linker {
+    args: Vec<OsString>,
+    post_encode: Option<PostEncodeHook>,
+}
+
+impl Linker {
+    /// Creates a new linker which will be run with `args`, not including the
+    /// program name.
+    pub fn new<I>(args: I) -> Linker
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        Linker {
+            args: args.into_iter().map(|a| a.into()).collect(),
+            post_encode: None,
+        }
+    }
+
+    /// Registers a hook which is invoked with the encoded component.
+    ///
+    /// The hook runs after the component has been encoded but before it's
+    /// validated and written to the output, so any modifications it makes are
+    /// subject to validation (unless `--validate-component=false` is passed)
+    /// and are reflected in the output. The hook is not invoked with `-shared`
+    /// since no component is produced in that case.
+    pub fn with_post_encode(mut self, hook: PostEncodeHook) -> Linker {
+        self.post_encode = Some(hook);
+        self
+    }
+
+    /// Runs `wasm-ld` and then creates a component from its output.
+    pub fn run(self) -> Result<()> {
+        let args = std::iter::once(OsString::from("wasm-component-ld")).chain(self.args);
+        let mut app = App::parse(args)?;
+        app.post_encode = self.post_encode;
+        app.run()
+    }
 }
 
 /// A linker to create a Component from input object files and libraries.
@@ -228,25 +309,143 @@ struct App {
 /// will internally invoke `wasm-ld`. After `wasm-ld` has been invoked the core
 /// wasm module will be turned into a component using component tooling and
 /// embedded information in the core wasm module.
+///
+/// Arguments after a literal `--` are forwarded verbatim and in order to
+/// `wasm-ld` after all other arguments.
+///
+/// Focused help on a few topics is available with `--help=TOPIC`, where
+/// `TOPIC` is one of `adapters`, `wit`, or `debugging-links`.
 #[derive(clap::Parser, Default)]
 #[command(version)]
 struct ComponentLdArgs {
     /// Which default WASI adapter, if any, to use when creating the output
     /// component.
+    ///
+    /// If not specified the `command` adapter is used when the core module
+    /// exports `_start` and the `reactor` adapter otherwise. A module which
+    /// exports both `_start` and `_initialize` is treated as a command, with
+    /// a warning.
     #[clap(long, name = "command|reactor|proxy|none")]
     wasi_adapter: Option<WasiAdapter>,
 
+    /// Use the `command` WASI adapter, the same as
+    /// `--wasi-adapter=command`.
+    #[clap(long, conflicts_with_all = ["is_reactor", "command|reactor|proxy|none"])]
+    is_command: bool,
+
+    /// Use the `reactor` WASI adapter, the same as
+    /// `--wasi-adapter=reactor`.
+    #[clap(long, conflicts_with = "command|reactor|proxy|none")]
+    is_reactor: bool,
+
     /// Location of where to find `wasm-ld`.
     ///
     /// If not specified this is automatically detected.
     #[clap(long, name = "PATH")]
     wasm_ld_path: Option<PathBuf>,
 
+    /// Which linker to prefer when automatically detecting one in `$PATH`.
+    ///
+    /// By default the first directory in `$PATH` with either `wasm-ld` or
+    /// `rust-lld` is used, preferring `wasm-ld` if both are present. With this
+    /// option all of `$PATH` is searched for the preferred linker before
+    /// falling back to the other one.
+    #[clap(long, value_name = "wasm-ld|rust-lld")]
+    linker_preference: Option<LinkerPreference>,
+
+    /// Name of the `wasm-ld` executable to search for in `$PATH`, such as
+    /// `wasm-ld-18`.
+    ///
+    /// This replaces `wasm-ld` in the automatic detection of a linker, which
+    /// still also searches for `rust-lld`.
+    #[clap(long, value_name = "NAME", default_value = "wasm-ld")]
+    lld_name: String,
+
+    /// Use `rust-lld` from the sysroot of the rustup toolchain `NAME`, such as
+    /// `nightly`.
+    ///
+    /// The sysroot is found with `rustc +NAME --print sysroot`. If that fails,
+    /// for example because rustup isn't installed, a warning is printed and
+    /// the linker is searched for in `$PATH` as usual. Ignored if
+    /// `--wasm-ld-path` is specified.
+    #[clap(long, value_name = "NAME")]
+    toolchain: Option<String>,
+
+    /// Target triple being linked for, such as `wasm32-wasip2`.
+    ///
+    /// This is checked against the core module produced by `wasm-ld`, and
+    /// selects the bundled WASI adapter unless `--wasi-adapter` is given:
+    ///
+    /// * `wasm32-wasi`, `wasm32-wasip1`, and `wasm32-wasip2` use the `command`
+    ///   or `reactor` adapter, detected from the core module as without
+    ///   `--target`.
+    /// * Targets which aren't WASI, such as `wasm32-unknown-unknown`, use no
+    ///   WASI adapter.
+    ///
+    /// Other WASI targets, such as `wasm32-wasip1-threads`, and `wasm64`
+    /// targets are rejected since no bundled adapter supports them.
+    #[clap(long, value_name = "TRIPLE")]
+    target: Option<Target>,
+
+    /// Optimization level for both `wasm-ld` and LTO.
+    ///
+    /// This is shorthand for passing `-O N` and `--lto-O=N` to `wasm-ld`.
+    /// Either of those passed explicitly takes precedence since they're
+    /// placed after these.
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u8).range(0..=3))]
+    opt_level: Option<u8>,
+
+    /// Set the environment variable `KEY` to `VALUE` for `wasm-ld`.
+    ///
+    /// This may be passed multiple times and doesn't affect the environment
+    /// of this process.
+    #[clap(long = "lld-env", value_name = "KEY=VALUE", value_parser = parse_lld_env)]
+    lld_env: Vec<(String, String)>,
+
+    /// Run `wasm-ld` with an empty environment, except for variables set with
+    /// `--lld-env`.
+    #[clap(long)]
+    lld_clear_env: bool,
+
+    /// How the core module produced by `wasm-ld` is held while the
+    /// component is created.
+    ///
+    /// With `file`, the default, `wasm-ld` writes the core module to a
+    /// temporary directory which is removed once linking finishes. With
+    /// `memory` the core module is read into memory and the temporary
+    /// directory is removed immediately after `wasm-ld` finishes.
+    #[clap(long, value_name = "memory|file")]
+    intermediate: Option<Intermediate>,
+
+    /// Kill `wasm-ld` and fail if it doesn't finish within `SECONDS`.
+    #[clap(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Print a line to stderr periodically while `wasm-ld` is running, if
+    /// stderr is a terminal.
+    ///
+    /// This is useful for long links, for example with LTO, which otherwise
+    /// print nothing until they finish.
+    #[clap(long)]
+    progress: bool,
+
+    /// Same as `--progress`, but also when stderr isn't a terminal.
+    #[clap(long)]
+    force_progress: bool,
+
+    /// Number of seconds between lines printed by `--progress`.
+    #[clap(long, value_name = "SECONDS", default_value_t = 10)]
+    progress_interval: u64,
+
     /// Quoting syntax for response files.
     #[clap(long, name = "STYLE")]
     rsp_quoting: Option<String>,
 
     /// Where to place the component output.
+    ///
+    /// The placeholders `{adapter}` and `{kind}` are both replaced with the
+    /// kind of WASI adapter used: "command", "reactor", "proxy", or "none".
+    /// With `-shared` no adapter is used so they're replaced with "none".
     #[clap(short, long)]
     output: Option<PathBuf>,
 
@@ -260,280 +459,3939 @@ struct ComponentLdArgs {
     #[clap(long)]
     validate_component: Option<bool>,
 
+    /// Validate the output component without validating the function bodies
+    /// of adapter modules.
+    ///
+    /// This works around adapters with function bodies that are known to be
+    /// invalid. Only the code within adapter modules is skipped: the types,
+    /// imports, exports, and other sections of adapters are still validated,
+    /// as are the instantiations which link them to the rest of the
+    /// component and everything outside of adapters. This covers the
+    /// built-in WASI adapter and those from `--adapt` and `--adapter-for`,
+    /// including ones renamed with `--adapter-name`.
+    ///
+    /// `wit-component` validates the adapters it's given itself, so this is
+    /// only useful when adapter code is changed afterwards, for example by a
+    /// post-encode hook of the `Linker` API.
+    #[clap(long)]
+    skip_adapter_validation: bool,
+
     /// Adapters to use when creating the final component.
     #[clap(long = "adapt", value_name = "[NAME=]MODULE", value_parser = parse_adapter)]
-    adapters: Vec<(String, Vec<u8>)>,
-}
+    adapters: Vec<Adapter>,
 
-fn parse_adapter(s: &str) -> Result<(String, Vec<u8>)> {
-    let (name, path) = parse_optionally_name_file(s);
-    let wasm = wat::parse_file(path)?;
-    Ok((name.to_string(), wasm))
-}
+    /// Satisfy imports from the core wasm module `MODULE` with the adapter
+    /// `ADAPTER`.
+    ///
+    /// This is similar to `--adapt MODULE=ADAPTER` except that it's an error
+    /// if the adapter doesn't export every function the core module imports
+    /// from `MODULE`. Using `wasi_snapshot_preview1` as `MODULE` replaces the
+    /// built-in WASI adapter.
+    #[clap(long = "adapter-for", value_name = "MODULE=ADAPTER", value_parser = parse_adapter_for)]
+    adapters_for: Vec<Adapter>,
 
-fn parse_optionally_name_file(s: &str) -> (&str, &str) {
-    let mut parts = s.splitn(2, '=');
-    let name_or_path = parts.next().unwrap();
-    match parts.next() {
-        Some(path) => (name_or_path, path),
-        None => {
-            let name = Path::new(name_or_path)
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap();
-            let name = match name.find('.') {
-                Some(i) => &name[..i],
-                None => name,
-            };
-            (name, name_or_path)
-        }
-    }
-}
+    /// Don't satisfy imports from the core wasm module `MODULE` with an
+    /// adapter, importing its functions from the component instead.
+    ///
+    /// The functions are imported from an interface named after the
+    /// kebab-case version of `MODULE`, for example `wasi-snapshot-preview1`,
+    /// and must only use numeric types. Using `wasi_snapshot_preview1` as
+    /// `MODULE` skips the built-in WASI adapter.
+    #[clap(long = "no-adapter-for", value_name = "MODULE")]
+    no_adapter_for: Vec<String>,
 
-#[derive(Debug, Copy, Clone)]
-enum WasiAdapter {
-    Command,
-    Reactor,
-    Proxy,
-    None,
-}
+    /// Directories of adapters to use when creating the final component.
+    ///
+    /// Every `*.wasm` and `*.wat` file in the directory is used as an adapter
+    /// named after the file, as with `--adapt` without a `NAME=`.
+    #[clap(long = "adapt-dir", value_name = "DIR")]
+    adapter_dirs: Vec<PathBuf>,
 
-impl FromStr for WasiAdapter {
-    type Err = anyhow::Error;
+    /// WIT file or directory representing additional component type
+    /// information to use.
+    ///
+    /// This may also be a WIT package encoded as a WebAssembly binary, as
+    /// distributed by registries.
+    ///
+    /// May be specified more than once, in which case all worlds are merged
+    /// together. Worlds which are structurally identical to one that was
+    /// already specified are ignored.
+    ///
+    /// See also the `--string-encoding` option.
+    #[clap(long = "component-type", value_name = "WIT")]
+    component_types: Vec<PathBuf>,
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "none" => Ok(WasiAdapter::None),
-            "command" => Ok(WasiAdapter::Command),
-            "reactor" => Ok(WasiAdapter::Reactor),
-            "proxy" => Ok(WasiAdapter::Proxy),
-            _ => bail!("unknown wasi adapter {s}, must be one of: none, command, reactor, proxy"),
-        }
-    }
-}
+    /// A WIT document, given directly as a string, representing additional
+    /// component type information to use.
+    ///
+    /// This behaves the same as `--component-type`, and worlds specified this
+    /// way are merged after those of all `--component-type` files.
+    #[clap(long = "component-type-inline", value_name = "WIT")]
+    component_types_inline: Vec<String>,
 
-pub fn main() {
-    let err = match run() {
-        Ok(()) => return,
-        Err(e) => e,
-    };
-    eprintln!("error: {err}");
-    if err.chain().len() > 1 {
-        eprintln!("\nCaused by:");
-        for (i, err) in err.chain().skip(1).enumerate() {
-            eprintln!("{i:>5}: {}", err.to_string().replace("\n", "\n       "));
-        }
-    }
+    /// WIT file or directory whose world takes precedence over those of
+    /// `--component-type` and `--component-type-inline`.
+    ///
+    /// These worlds are merged after all others. Any import or export of
+    /// previously merged worlds which has the same name as, or refers to the
+    /// same interface as, an import or export of this world is removed before
+    /// merging, so this world's definition wins instead of the conflict being
+    /// an error. May be specified more than once, in which case later files
+    /// take precedence over earlier ones.
+    #[clap(long = "component-type-override", value_name = "WIT")]
+    component_type_overrides: Vec<PathBuf>,
 
-    std::process::exit(1);
-}
+    /// Name of the world to use from `--component-type` packages.
+    ///
+    /// This is required for packages which contain more than one world, and
+    /// it's an error if a package doesn't contain a world of this name.
+    #[clap(long, value_name = "NAME")]
+    world: Option<String>,
 
-fn run() -> Result<()> {
-    App::parse()?.run()
-}
+    /// String encoding to use when creating the final component.
+    ///
+    /// This may be either "utf8", "utf16", or "compact-utf16" and defaults to
+    /// "utf8". This value is only used when one or more `--component-type`
+    /// options are specified. It's an error to pass an encoding that differs
+    /// from one already recorded in the core module's component type
+    /// metadata.
+    #[clap(long, value_parser = parse_encoding, value_name = "ENCODING")]
+    string_encoding: Option<StringEncoding>,
 
-impl App {
-    /// Parse the CLI arguments into an `App` to run the linker.
+    /// Print, for each injected adapter, which imports of the core module it
+    /// satisfied and which of its own imports remain in the final component.
+    #[clap(long)]
+    adapter_trace: bool,
+
+    /// Write a file listing the inputs of this link, suitable for build
+    /// systems to track dependencies of the output.
+    #[clap(long, value_name = "PATH")]
+    dep_file: Option<PathBuf>,
+
+    /// Format of the file written by `--dep-file`.
     ///
-    /// This is unfortunately nontrivial because the way `wasm-ld` takes
-    /// arguments is not compatible with `clap`. Namely flags like
-    /// `--whole-archive` are positional are processed in a stateful manner.
-    /// This means that the relative ordering of flags to `wasm-ld` needs to be
-    /// preserved. Additionally there are flags like `-shared` which clap does
-    /// not support.
+    /// This defaults to `make`, a Makefile fragment as understood by Make and
+    /// Ninja. The `json` format is an object with the `output` path and an
+    /// `inputs` object listing `objects`, `wit`, and `adapters` separately.
+    #[clap(long, value_name = "make|json")]
+    dep_file_format: Option<DepFileFormat>,
+
+    /// Reorder the sections of the core wasm module produced by `wasm-ld`
+    /// into the canonical order before creating the component.
     ///
-    /// To handle this the `lexopt` crate is used to perform low-level argument
-    /// parsing. That's then used to determine whether the argument is intended
-    /// for `wasm-component-ld` or `wasm-ld`, so arguments are filtered into two
-    /// lists. Using these lists the arguments to `wasm-component-ld` are then
-    /// parsed. On failure a help message is presented with all `wasm-ld`
-    /// arguments added as well.
+    /// Non-custom sections keep the order mandated by the core wasm
+    /// specification. Custom sections which precede all other sections stay
+    /// at the start of the module and all other custom sections are moved to
+    /// the end, keeping their relative order.
+    #[clap(long)]
+    canonicalize_sections: bool,
+
+    /// Turn warnings emitted by `wasm-component-ld` itself into errors.
     ///
-    /// This means that functionally it looks like `clap` parses everything when
-    /// in fact `lexopt` is used to filter out `wasm-ld` arguments and `clap`
-    /// only parses arguments specific to `wasm-component-ld`.
-    fn parse() -> Result<App> {
-        let mut args = env::args_os().collect::<Vec<_>>();
+    /// This is independent of `--fatal-warnings`, which is forwarded to
+    /// `wasm-ld` and only affects its own warnings.
+    #[clap(long)]
+    fail_on_warnings: bool,
 
-        // First remove `-flavor wasm` in case this is invoked as a generic LLD
-        // driver. We can safely ignore that going forward.
-        if let Some([flavor, wasm]) = args.get(1..3) {
-            if flavor == "-flavor" && wasm == "wasm" {
-                args.remove(1);
-                args.remove(1);
-            }
-        }
+    /// Print the top-level sections of the final component to stderr.
+    ///
+    /// Each section's kind and size is printed in order, along with the name
+    /// of custom sections.
+    #[clap(long)]
+    list_sections: bool,
 
-        let mut command = ComponentLdArgs::command();
-        let mut lld_args = Vec::new();
-        let mut component_ld_args = vec![std::env::args_os().nth(0).unwrap()];
-        let mut shared = false;
-        let mut parser = lexopt::Parser::from_iter(args);
+    /// Ensure that the import `NAME` is not part of the final component.
+    ///
+    /// Imports which nothing references, such as unused imports of a
+    /// `--component-type` world, are already left out of the component. This
+    /// flag asserts that's the case for `NAME` and fails the link if the
+    /// import is actually used by the core module or an adapter.
+    #[clap(long = "drop-import", value_name = "NAME")]
+    drop_imports: Vec<String>,
 
-        fn handle_lld_arg(
-            lld: &LldFlag,
-            parser: &mut lexopt::Parser,
-            lld_args: &mut Vec<OsString>,
-        ) -> Result<()> {
-            let mut arg = OsString::new();
-            match (lld.short, lld.long) {
-                (_, Some(long)) => {
-                    arg.push("--");
-                    arg.push(long);
-                }
-                (Some(short), _) => {
-                    arg.push("-");
-                    arg.push(short.encode_utf8(&mut [0; 5]));
-                }
-                (None, None) => unreachable!(),
-            }
-            match lld.value {
-                FlagValue::None => {
-                    lld_args.push(arg);
-                }
+    /// Use the exported core function `NAME` as the canonical ABI `realloc`
+    /// function instead of `cabi_realloc`.
+    ///
+    /// `NAME` must be exported from the core module with the signature
+    /// `(i32, i32, i32, i32) -> i32`. It's additionally exported as
+    /// `cabi_realloc`, replacing any existing export of that name.
+    #[clap(long, value_name = "NAME")]
+    realloc: Option<String>,
 
-                FlagValue::RequiredSpace(_) => {
-                    lld_args.push(arg);
-                    lld_args.push(parser.value()?);
-                }
+    /// Export ABI helpers such as `cabi_realloc` from the final component.
+    ///
+    /// By default helpers like `cabi_realloc` are only used internally by
+    /// the component's canonical ABI options. With this flag they're also
+    /// lifted and exported from the component under a kebab-case name, for
+    /// example `cabi-realloc`.
+    #[clap(long, overrides_with = "hide_abi_helpers")]
+    keep_abi_helpers: bool,
 
-                FlagValue::RequiredEqual(_) => {
-                    arg.push("=");
-                    arg.push(&parser.value()?);
-                    lld_args.push(arg);
-                }
+    /// Export all functions of the core module from the final component.
+    ///
+    /// Each exported core function whose signature only uses numeric types
+    /// is exported from the component under the kebab-case version of its
+    /// name, for example `my_func` as `my-func`. Functions already exported
+    /// through the component type, or whose names can't be made kebab-case,
+    /// are skipped. Note that this may export ABI helpers like
+    /// `cabi_realloc` as well, for which a warning is printed.
+    #[clap(long)]
+    component_export_all: bool,
 
-                // If the value is optional then the argument must have an `=`
-                // in the argument itself.
-                FlagValue::Optional(_) => {
-                    match parser.optional_value() {
-                        Some(val) => {
-                            arg.push("=");
-                            arg.push(&val);
-                        }
-                        None => {}
-                    }
-                    lld_args.push(arg);
-                }
-            }
-            Ok(())
-        }
+    /// Don't export ABI helpers from the final component, the default.
+    #[clap(long, overrides_with = "keep_abi_helpers")]
+    hide_abi_helpers: bool,
 
-        loop {
-            if let Some(mut args) = parser.try_raw_args() {
-                if let Some(arg) = args.peek() {
-                    let for_lld = LLD_LONG_FLAGS_NONSTANDARD.iter().any(|s| arg == *s);
-                    if for_lld {
-                        lld_args.push(arg.to_owned());
-                        if arg == "-shared" {
-                            shared = true;
-                        }
-                        args.next();
-                        continue;
-                    }
-                }
-            }
+    /// Require that the component is emitted in binary format version `N`.
+    ///
+    /// Currently only version 13 (`0xd`), the version of the component model
+    /// binary format at the time of this writing, is supported. Requesting
+    /// any other version is an error.
+    #[clap(long, value_name = "N")]
+    format_version: Option<u16>,
 
-            match parser.next()? {
-                Some(Arg::Value(obj)) => {
-                    lld_args.push(obj);
-                }
-                Some(Arg::Short(c)) => match LLD_FLAGS.iter().find(|f| f.short == Some(c)) {
-                    Some(lld) => {
-                        handle_lld_arg(lld, &mut parser, &mut lld_args)?;
-                    }
-                    None => {
-                        component_ld_args.push(format!("-{c}").into());
-                        if let Some(arg) =
-                            command.get_arguments().find(|a| a.get_short() == Some(c))
-                        {
-                            if let ArgAction::Set = arg.get_action() {
-                                component_ld_args.push(parser.value()?);
-                            }
-                        }
-                    }
-                },
-                Some(Arg::Long(c)) => match LLD_FLAGS.iter().find(|f| f.long == Some(c)) {
-                    Some(lld) => {
-                        handle_lld_arg(lld, &mut parser, &mut lld_args)?;
-                    }
-                    None => {
-                        component_ld_args.push(format!("--{c}").into());
-                        if let Some(arg) = command.get_arguments().find(|a| a.get_long() == Some(c))
-                        {
-                            if let ArgAction::Set = arg.get_action() {
-                                component_ld_args.push(parser.value()?);
-                            }
-                        }
-                    }
-                },
-                None => break,
-            }
-        }
+    /// Embed information about how the component was built in a
+    /// `wasm-component-ld-build-info` custom section.
+    ///
+    /// The section contains JSON with the version of `wasm-component-ld`, the
+    /// version reported by `wasm-ld`, the arguments this tool was invoked
+    /// with, and a timestamp unless `--deterministic` is passed.
+    #[clap(long)]
+    embed_build_info: bool,
 
-        match command.try_get_matches_from_mut(component_ld_args.clone()) {
-            Ok(matches) => Ok(App {
-                component: ComponentLdArgs::from_arg_matches(&matches)?,
-                lld_args,
-                shared,
-            }),
-            Err(_) => {
-                add_wasm_ld_options(ComponentLdArgs::command()).get_matches_from(component_ld_args);
-                unreachable!();
-            }
-        }
-    }
+    /// Embed the WIT world of the component, in the text format, in a
+    /// `wasm-component-ld-wit` custom section.
+    ///
+    /// This makes the component self-describing for tools which inspect
+    /// custom sections rather than decoding its type.
+    #[clap(long)]
+    embed_wit: bool,
 
-    fn run(&mut self) -> Result<()> {
-        let mut cmd = self.lld();
-        let linker = cmd.get_program().to_owned();
+    /// Avoid embedding nondeterministic information, such as timestamps, in
+    /// the output.
+    #[clap(long)]
+    deterministic: bool,
 
-        let lld_output =
-            tempfile::NamedTempFile::new().context("failed to create temp output file")?;
+    /// Print the bundled WASI adapter of the given kind and exit.
+    ///
+    /// The adapter is written to `--output` if specified or stdout otherwise.
+    #[clap(long, value_name = "command|reactor|proxy")]
+    print_adapter: Option<WasiAdapter>,
+
+    /// Print the adapter from `--print-adapter` in the text format instead of
+    /// the binary format.
+    #[clap(long, requires = "print_adapter")]
+    output_wat: bool,
+
+    /// Redirect the imported function `FROM` to the function `TO`.
+    ///
+    /// Both functions are named as `interface#function`, for example
+    /// `wasi:random/random@0.2.0#get-random-bytes`, and must be imports of the
+    /// core module's component type world. The functions must have the same
+    /// lowered core wasm signature.
+    #[clap(long = "rename-import-func", value_name = "FROM=TO", value_parser = parse_rename)]
+    rename_import_funcs: Vec<(String, String)>,
+
+    /// Sort the imports of the final component by name.
+    ///
+    /// By default imports are ordered by when they're first encountered,
+    /// which can depend on the order of input objects. Combined with
+    /// `--deterministic` this produces reproducible output.
+    #[clap(long)]
+    sort_imports: bool,
+
+    /// WIT file whose world is compared against the final component with
+    /// `--print-world-diff`.
+    #[clap(long, value_name = "PATH")]
+    baseline_wit: Option<PathBuf>,
+
+    /// Print the imports and exports that were added to or removed from the
+    /// final component relative to the world of `--baseline-wit`.
+    ///
+    /// This only reports differences and never fails the link.
+    #[clap(long, requires = "baseline_wit")]
+    print_world_diff: bool,
+
+    /// Registry package name, such as `my-org:my-component`, to record as the
+    /// name of the component.
+    #[clap(long, value_name = "NAMESPACE:NAME", value_parser = parse_package_name)]
+    package_name: Option<String>,
+
+    /// Version of the package named by `--package-name`.
+    ///
+    /// This must be a valid semver version and is recorded as part of the
+    /// component's name, as in `my-org:my-component@1.0.0`.
+    #[clap(long, value_name = "VERSION", requires = "package_name")]
+    package_version: Option<semver::Version>,
+
+    /// Authors of the package to record in the component's registry
+    /// metadata.
+    ///
+    /// This may be passed multiple times or as a comma-separated list.
+    #[clap(long, value_name = "AUTHOR", value_delimiter = ',')]
+    authors: Vec<String>,
+
+    /// Human-readable description of the package to record in the
+    /// component's registry metadata.
+    #[clap(long, value_name = "TEXT")]
+    description: Option<String>,
+
+    /// Additional import module names, such as `wasi_unstable`, which are
+    /// also satisfied by the built-in WASI adapter.
+    ///
+    /// A separate instance of the adapter is injected for each alias. It's
+    /// an error for the core module to import from a `wasi_*` module other
+    /// than `wasi_snapshot_preview1` which isn't listed here or handled by
+    /// another adapter.
+    #[clap(long, value_name = "LIST", value_delimiter = ',')]
+    preview1_aliases: Vec<String>,
+
+    /// Write a CycloneDX SBOM describing the final component to `PATH`.
+    ///
+    /// The SBOM is JSON and lists the component, the version of
+    /// `wasm-component-ld`, the adapters used, and the WIT packages the
+    /// component's imports and exports come from.
+    #[clap(long, value_name = "PATH")]
+    sbom_out: Option<PathBuf>,
+
+    /// Validate the existing component at `PATH` and exit without linking.
+    #[clap(long, value_name = "PATH")]
+    validate_only: Option<PathBuf>,
+
+    /// Load and merge all `--component-type` inputs, then exit without
+    /// linking.
+    ///
+    /// This is a quick check that WIT files are compatible with each other.
+    #[clap(long)]
+    check_component_types: bool,
+
+    /// Declare that the adapter `NAME` uses the string encoding `ENCODING`.
+    ///
+    /// Each adapter's string encoding comes from its own embedded component
+    /// type metadata rather than `--string-encoding`. This verifies that the
+    /// metadata of the adapter matches `ENCODING` and fails the link
+    /// otherwise.
+    #[clap(long = "adapt-encoding", value_name = "NAME=ENCODING", value_parser = parse_adapter_encoding)]
+    adapter_encodings: Vec<(String, StringEncoding)>,
+
+    /// Reorder the objects and archives passed to `wasm-ld` to match `FILE`.
+    ///
+    /// `FILE` lists one object per line, exactly as passed on the command
+    /// line, and blank lines and lines starting with `#` are ignored. Objects
+    /// which aren't listed are placed after all listed ones in their original
+    /// order. It's an error for `FILE` to list an object which wasn't passed.
+    #[clap(long, value_name = "FILE")]
+    objects_order: Option<PathBuf>,
+
+    /// Pass each line of `FILE` to `wasm-ld` as an input object, in place of
+    /// this flag.
+    ///
+    /// Unlike a response file, lines are never interpreted as flags, so
+    /// file names starting with `-` are passed as objects too. Blank lines
+    /// are ignored. This may be passed multiple times.
+    #[clap(long = "object-manifest", value_name = "FILE")]
+    object_manifests: Vec<PathBuf>,
+
+    /// Fail the link if the component has more than `N` imports.
+    #[clap(long, value_name = "N")]
+    max_imports: Option<usize>,
+
+    /// Fail the link if the component has more than `N` exports.
+    #[clap(long, value_name = "N")]
+    max_exports: Option<usize>,
+
+    /// Print warnings and errors as GitHub Actions workflow commands.
+    ///
+    /// Messages are printed as `::warning::MESSAGE` and `::error::MESSAGE`
+    /// so they're shown as annotations in GitHub Actions.
+    #[clap(long)]
+    github_annotations: bool,
+
+    /// Write debugging artifacts to files named after `PREFIX`.
+    ///
+    /// This writes the core module given to the component encoder to
+    /// `PREFIX.core.wasm`, a copy of the component to `PREFIX.wasm`, the
+    /// component's world to `PREFIX.wit`, and a JSON manifest listing these
+    /// files along with the component's adapters, imports, and exports to
+    /// `PREFIX.json`. The directory of `PREFIX` is created if necessary.
+    #[clap(long, value_name = "PREFIX")]
+    artifacts_prefix: Option<PathBuf>,
+
+    /// Fail the link if any import of the component doesn't have a version,
+    /// such as `wasi:cli/stdout@0.2.0`.
+    ///
+    /// Note that imports which aren't part of a package, such as a
+    /// top-level function `foo`, never have a version and are rejected too.
+    #[clap(long)]
+    reject_imports_without_version: bool,
+
+    /// Rename imports of the component from packages matching `PACKAGE` to
+    /// use version `VERSION`, such as `wasi@0.2.0`.
+    ///
+    /// `PACKAGE` is either a namespace, such as `wasi`, or a package, such as
+    /// `wasi:io`. For example with `wasi@0.2.0` the import
+    /// `wasi:io/streams@0.2.1` is renamed to `wasi:io/streams@0.2.0`. The
+    /// versions must be semver-compatible. This may be passed multiple
+    /// times.
+    #[clap(long, value_name = "PACKAGE@VERSION", value_parser = parse_normalize_imports)]
+    normalize_imports: Vec<(String, semver::Version)>,
+
+    /// Compose the component with the dependency component at `PATH`.
+    ///
+    /// Each import of the component which `PATH` exports under the same
+    /// name, such as an interface `wasi:keyvalue/store@0.2.0`, is satisfied
+    /// with that export. The output is a component which instantiates both,
+    /// exports the exports of this component, and imports whatever remains
+    /// unsatisfied. It's an error if no imports are satisfied by `PATH`.
+    #[clap(long, value_name = "PATH")]
+    compose_with: Option<PathBuf>,
+
+    /// Check the `target_features` of input objects for conflicts before
+    /// running `wasm-ld`.
+    ///
+    /// The link fails if an object uses a feature, such as
+    /// `mutable-globals`, which another object disallows. Inputs which aren't
+    /// wasm object files, such as archives, aren't checked.
+    #[clap(long)]
+    pre_link_check: bool,
+
+    /// Print the imports of the core module produced by `wasm-ld`, as
+    /// `module.field`, to stderr.
+    #[clap(long)]
+    print_core_imports: bool,
+
+    /// Print the memory settings of the core module produced by `wasm-ld` to
+    /// stderr.
+    ///
+    /// This prints the initial and maximum size of the linear memory, as set
+    /// with `--initial-memory` and `--max-memory`, and the initial values of
+    /// the `__stack_pointer` and `__heap_base` globals. With `--stack-first`
+    /// the initial stack pointer is the stack size set with
+    /// `-z stack-size=N`. Globals are found through the `name` section so
+    /// they're missing if it was stripped.
+    #[clap(long)]
+    print_memory_config: bool,
+
+    /// Print which WASI adapter is used, one of `command`, `reactor`,
+    /// `proxy`, or `none`.
+    ///
+    /// Unless chosen with `--wasi-adapter` or similar flags this is the
+    /// result of inspecting the exports of the core module, so this shows
+    /// whether it was detected as a command or a reactor. Linking continues
+    /// as usual afterwards.
+    #[clap(long)]
+    print_adapter_kind: bool,
+
+    /// Write a WIT world with just the imports of the component to `PATH`.
+    ///
+    /// This is a starting point for a host which runs the component, for
+    /// example in tests, as it lists everything the host needs to provide.
+    #[clap(long, value_name = "PATH")]
+    emit_host_stub: Option<PathBuf>,
+
+    /// Write the WIT of the component type metadata embedded in the core
+    /// module to `PATH`.
+    ///
+    /// This is the metadata just before the component is created, including
+    /// that of `--component-type` and of the input objects, and is useful to
+    /// check what made it into the core module.
+    #[clap(long, value_name = "PATH")]
+    emit_core_wit: Option<PathBuf>,
+
+    /// What to do with symbols left undefined by `wasm-ld`, for example with
+    /// `--allow-undefined`.
+    ///
+    /// `wasm-ld` turns undefined functions into imports from the `env`
+    /// module. With `import` each of these becomes a function import of the
+    /// component under the kebab-case version of its name, which requires
+    /// its signature to only use numeric types. With `error` the link fails
+    /// listing all such symbols. By default they're left as-is, which fails
+    /// when the component is encoded unless an adapter named `env` is given
+    /// with `--adapt`, in which case this option is ignored.
+    #[clap(long, value_name = "import|error")]
+    on_unresolved: Option<OnUnresolved>,
+
+    /// Fail instead of using a generic name for the temporary core module
+    /// when `--output` has no usable file name.
+    ///
+    /// The temporary core module is normally named after the file name of
+    /// `--output`, falling back to `output.wasm` if there isn't one, such as
+    /// with `-o ..`.
+    #[clap(long)]
+    strict_temp: bool,
+
+    /// Print the name and version of each WIT package used by
+    /// `--component-type` to stderr, including dependencies, after all
+    /// inputs have been merged.
+    #[clap(long)]
+    print_wit_packages: bool,
+
+    /// Print each step of loading and merging `--component-type` inputs to
+    /// stderr.
+    ///
+    /// For each input this prints the packages it contributed, the world
+    /// selected from it, and each merge into the worlds of previous inputs,
+    /// which shows how far merging got when it fails.
+    #[clap(long)]
+    trace_wit_merge: bool,
+
+    /// Name the core module of the adapter `NAME` as `LABEL` in the final
+    /// component.
+    ///
+    /// By default the module of each adapter is named
+    /// `wit-component:adapter:NAME`, for example
+    /// `wit-component:adapter:wasi_snapshot_preview1`.
+    #[clap(long = "adapter-name", value_name = "NAME=LABEL", value_parser = parse_adapter_name)]
+    adapter_names: Vec<(String, String)>,
+
+    /// Link twice and fail if the two outputs differ.
+    #[clap(long, hide = true)]
+    check_determinism: bool,
+
+    /// Also write the core module to `PATH`, in addition to the component.
+    ///
+    /// This is the `wasm32-wasip1` core module which the component was
+    /// created from, including its embedded component type metadata, such
+    /// that it can be distributed alongside the component.
+    #[clap(long, value_name = "PATH")]
+    also_emit_core: Option<PathBuf>,
+
+    /// Sign the final component with the ed25519 key in `FILE` and write the
+    /// detached signature to the output path with `.sig` appended.
+    ///
+    /// `FILE` contains the 32-byte secret key, either as raw bytes or as 64
+    /// hexadecimal digits. The signature is the raw 64-byte ed25519
+    /// signature of the bytes of the output file.
+    #[clap(long, value_name = "FILE")]
+    sign_key: Option<PathBuf>,
+
+    /// Whether the component may import interfaces outside of the
+    /// `wasi:http/proxy` world.
+    ///
+    /// With `strict` the link fails if the component imports anything which
+    /// isn't part of the `wasi:http/proxy` world, regardless of version,
+    /// which catches unexpected imports in components built with the `proxy`
+    /// adapter. The default, `permissive`, doesn't check imports.
+    #[clap(long, value_name = "permissive|strict")]
+    proxy_imports: Option<ProxyImports>,
+
+    /// Keep the temporary directory holding the output of `wasm-ld` if
+    /// linking fails, printing its location.
+    ///
+    /// The temporary directory is still removed when linking succeeds.
+    #[clap(long)]
+    no_temp_cleanup_on_error: bool,
+
+    /// Fail the link if there's no component type information, neither from
+    /// `--component-type` and related flags nor embedded in input objects.
+    ///
+    /// Without any the component's world is inferred from the core module's
+    /// imports alone, which is rarely intended when a WIT world should be
+    /// used.
+    #[clap(long)]
+    require_component_type: bool,
+
+    /// Warn about each `--export=SYM` passed to `wasm-ld` which isn't
+    /// exported from the linked core module, for example due to a typo.
+    #[clap(long)]
+    verify_exports: bool,
+
+    /// Check that the core modules of the final component can be
+    /// instantiated, beyond validating it.
+    ///
+    /// This catches active data and element segments with constant offsets
+    /// which are out of bounds of the initial size of the memory or table
+    /// they initialize, which otherwise only fail at runtime.
+    #[clap(long)]
+    smoke_test: bool,
+
+    /// Remove the `producers` custom section of the core module before
+    /// creating the component.
+    ///
+    /// The component has its own `producers` section, so this avoids
+    /// duplicate provenance information within the final component.
+    #[clap(long)]
+    strip_producers_before_embed: bool,
+
+    /// Read defaults for this linker's own flags from the TOML file `FILE`.
+    ///
+    /// The supported keys are `wasi-adapter`, `string-encoding`,
+    /// `validate-component`, and `append-lld-flags`, the last of which is a
+    /// list of arguments passed to `wasm-ld` after all others except those
+    /// following `--`. Flags passed on the command line take precedence over
+    /// values in the file.
+    #[clap(long, value_name = "FILE.toml")]
+    config: Option<PathBuf>,
+}
+
+/// An adapter specified with `--adapt`.
+#[derive(Clone)]
+struct Adapter {
+    name: String,
+    path: PathBuf,
+    wasm: Vec<u8>,
+}
+
+fn parse_adapter(s: &str) -> Result<Adapter> {
+    let (name, path) = parse_optionally_name_file(s);
+    let wasm = wat::parse_file(path)?;
+    Ok(Adapter {
+        name: name.to_string(),
+        path: path.into(),
+        wasm,
+    })
+}
+
+fn parse_adapter_for(s: &str) -> Result<Adapter> {
+    let Some((module, path)) = s.split_once('=') else {
+        bail!("expected `MODULE=ADAPTER`, found `{s}`");
+    };
+    let wasm = wat::parse_file(path)?;
+    Ok(Adapter {
+        name: module.to_string(),
+        path: path.into(),
+        wasm,
+    })
+}
+
+fn parse_package_name(s: &str) -> Result<String> {
+    fn valid(part: &str) -> bool {
+        part.split('-').all(|word| {
+            word.starts_with(|c: char| c.is_ascii_lowercase())
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+    }
+    match s.split_once(':') {
+        Some((namespace, name)) if valid(namespace) && valid(name) => Ok(s.to_string()),
+        _ => bail!(
+            "invalid package name `{s}`, expected `namespace:name` with kebab-case identifiers"
+        ),
+    }
+}
+
+/// Checks that the `target_features` sections of the wasm `objects` don't
+/// conflict with each other, for `--pre-link-check`.
+fn check_object_features(objects: &[OsString]) -> Result<()> {
+    let mut used = std::collections::BTreeMap::new();
+    let mut disallowed = std::collections::BTreeMap::new();
+    let mut conflicts = Vec::new();
+    for object in objects {
+        let path = Path::new(object);
+        let Ok(wasm) = std::fs::read(path) else {
+            continue;
+        };
+        if !wasm.starts_with(b"\0asm") {
+            continue;
+        }
+        let features = target_features(&wasm)
+            .with_context(|| format!("failed to read target features of {path:?}"))?;
+        for (prefix, feature) in features {
+            match prefix {
+                b'+' | b'=' => {
+                    if let Some(other) = disallowed.get(&feature) {
+                        conflicts.push(format!(
+                            "{path:?} uses feature `{feature}` which {other:?} disallows"
+                        ));
+                    }
+                    used.entry(feature).or_insert(path);
+                }
+                b'-' => {
+                    if let Some(other) = used.get(&feature) {
+                        conflicts.push(format!(
+                            "{path:?} disallows feature `{feature}` which {other:?} uses"
+                        ));
+                    }
+                    disallowed.entry(feature).or_insert(path);
+                }
+                _ => bail!("invalid target feature prefix {prefix:#x} in {path:?}"),
+            }
+        }
+    }
+    if !conflicts.is_empty() {
+        bail!(
+            "input objects have conflicting target features:\n  {}",
+            conflicts.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Returns the entries of the `target_features` custom section of `wasm` as
+/// pairs of prefix and feature name.
+fn target_features(wasm: &[u8]) -> Result<Vec<(u8, String)>> {
+    let mut ret = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        let Payload::CustomSection(s) = payload? else {
+            continue;
+        };
+        if s.name() != "target_features" {
+            continue;
+        }
+        let mut reader =
+            wasmparser::BinaryReader::new(s.data(), s.data_offset(), WasmFeatures::all());
+        for _ in 0..reader.read_var_u32()? {
+            let prefix = reader.read_u8()?;
+            ret.push((prefix, reader.read_string()?.to_string()));
+        }
+    }
+    Ok(ret)
+}
+
+/// Reorders the objects at `positions` within `lld_args` to match the order
+/// listed in the `--objects-order` file at `path`.
+///
+/// Returns the objects in their new order.
+fn reorder_objects(
+    path: &Path,
+    lld_args: &mut [OsString],
+    positions: &[usize],
+) -> Result<Vec<OsString>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read objects order file {path:?}"))?;
+    let mut remaining = positions
+        .iter()
+        .map(|i| lld_args[*i].clone())
+        .collect::<Vec<_>>();
+    let mut ordered = Vec::new();
+    for line in contents.lines().map(|l| l.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match remaining.iter().position(|obj| obj == line) {
+            Some(i) => ordered.push(remaining.remove(i)),
+            None => bail!("object `{line}` listed in {path:?} was not passed to the linker"),
+        }
+    }
+    ordered.extend(remaining);
+    for (i, obj) in positions.iter().zip(&ordered) {
+        lld_args[*i] = obj.clone();
+    }
+    Ok(ordered)
+}
+
+/// Waits for `child` to exit, killing it and returning `None` if it's still
+/// running after `timeout`.
+///
+/// With `progress` a line is printed to stderr each time that much time has
+/// passed while waiting.
+fn wait_child(
+    child: &mut Child,
+    timeout: Option<Duration>,
+    progress: Option<Duration>,
+) -> Result<Option<ExitStatus>> {
+    let start = Instant::now();
+    let mut next_progress = progress.map(|p| start + p);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if timeout.is_some_and(|timeout| now >= start + timeout) {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        if let (Some(next), Some(interval)) = (&mut next_progress, progress) {
+            if now >= *next {
+                eprintln!("still linking ({}s elapsed)", (now - start).as_secs());
+                *next += interval;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Applies the defaults in the `--config` file at `path` to all of `args`
+/// which weren't specified on the command line.
+fn apply_config(
+    path: &Path,
+    matches: &clap::ArgMatches,
+    args: &mut ComponentLdArgs,
+    lld_args: &mut Vec<OsString>,
+) -> Result<()> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file {path:?}"))?;
+    let table = contents
+        .parse::<toml::Table>()
+        .with_context(|| format!("failed to parse config file {path:?}"))?;
+    let on_command_line = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    for (key, value) in table {
+        let expected = |ty: &str| format!("`{key}` in config file {path:?} must be {ty}");
+        match key.as_str() {
+            "wasi-adapter" => {
+                let value = value.as_str().with_context(|| expected("a string"))?;
+                if !on_command_line("command|reactor|proxy|none")
+                    && !on_command_line("is_command")
+                    && !on_command_line("is_reactor")
+                {
+                    args.wasi_adapter = Some(value.parse()?);
+                }
+            }
+            "string-encoding" => {
+                let value = value.as_str().with_context(|| expected("a string"))?;
+                if !on_command_line("string_encoding") {
+                    args.string_encoding = Some(parse_encoding(value)?);
+                }
+            }
+            "validate-component" => {
+                let value = value.as_bool().with_context(|| expected("a boolean"))?;
+                if !on_command_line("validate_component") {
+                    args.validate_component = Some(value);
+                }
+            }
+            "append-lld-flags" => {
+                let flags = value
+                    .as_array()
+                    .and_then(|flags| flags.iter().map(|f| f.as_str()).collect::<Option<Vec<_>>>())
+                    .with_context(|| expected("a list of strings"))?;
+                lld_args.extend(flags.into_iter().map(OsString::from));
+            }
+            _ => bail!(
+                "unknown key `{key}` in config file {path:?}, must be one of: \
+                 wasi-adapter, string-encoding, validate-component, append-lld-flags"
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn parse_adapter_name(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((name, label)) => Ok((name.to_string(), label.to_string())),
+        None => bail!("expected `NAME=LABEL`, found `{s}`"),
+    }
+}
+
+fn parse_lld_env(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some(("", _)) => bail!("empty variable name in `{s}`"),
+        Some((key, value)) => Ok((key.to_string(), value.to_string())),
+        None => bail!("expected `KEY=VALUE`, found `{s}`"),
+    }
+}
+
+fn parse_normalize_imports(s: &str) -> Result<(String, semver::Version)> {
+    let Some((package, version)) = s.split_once('@') else {
+        bail!("expected `PACKAGE@VERSION`, found `{s}`");
+    };
+    let version = version
+        .parse()
+        .with_context(|| format!("invalid version in `{s}`"))?;
+    Ok((package.to_string(), version))
+}
+
+fn parse_adapter_encoding(s: &str) -> Result<(String, StringEncoding)> {
+    let Some((name, encoding)) = s.split_once('=') else {
+        bail!("expected `NAME=ENCODING`, found `{s}`");
+    };
+    Ok((name.to_string(), parse_encoding(encoding)?))
+}
+
+fn parse_encoding(s: &str) -> Result<StringEncoding> {
+    Ok(match s.to_ascii_lowercase().replace('_', "-").as_str() {
+        "utf8" => StringEncoding::UTF8,
+        "utf16" => StringEncoding::UTF16,
+        "compact-utf16" => StringEncoding::CompactUTF16,
+        _ => bail!("unknown string encoding `{s}`, must be one of: utf8, utf16, compact-utf16"),
+    })
+}
+
+fn parse_rename(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((from, to)) => Ok((from.to_string(), to.to_string())),
+        None => bail!("expected `FROM=TO`, found `{s}`"),
+    }
+}
+
+fn parse_optionally_name_file(s: &str) -> (&str, &str) {
+    let mut parts = s.splitn(2, '=');
+    let name_or_path = parts.next().unwrap();
+    match parts.next() {
+        Some(path) => (name_or_path, path),
+        None => (default_adapter_name(Path::new(name_or_path)), name_or_path),
+    }
+}
+
+/// Returns the name of the adapter at `path` when one isn't explicitly given,
+/// which is its file name up to the first `.`.
+fn default_adapter_name(path: &Path) -> &str {
+    let name = path.file_name().unwrap().to_str().unwrap();
+    match name.find('.') {
+        Some(i) => &name[..i],
+        None => name,
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum WasiAdapter {
+    Command,
+    Reactor,
+    Proxy,
+    None,
+}
+
+impl FromStr for WasiAdapter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(WasiAdapter::None),
+            "command" => Ok(WasiAdapter::Command),
+            "reactor" => Ok(WasiAdapter::Reactor),
+            "proxy" => Ok(WasiAdapter::Proxy),
+            _ => bail!("unknown wasi adapter {s}, must be one of: none, command, reactor, proxy"),
+        }
+    }
+}
+
+impl WasiAdapter {
+    /// Returns the bundled adapter module for this kind of adapter, if any.
+    fn wasm(&self) -> Option<&'static [u8]> {
+        match self {
+            WasiAdapter::Command => Some(include_bytes!("wasi_snapshot_preview1.command.wasm")),
+            WasiAdapter::Reactor => Some(include_bytes!("wasi_snapshot_preview1.reactor.wasm")),
+            WasiAdapter::Proxy => Some(include_bytes!("wasi_snapshot_preview1.proxy.wasm")),
+            WasiAdapter::None => None,
+        }
+    }
+}
+
+impl fmt::Display for WasiAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WasiAdapter::None => "none",
+            WasiAdapter::Command => "command",
+            WasiAdapter::Reactor => "reactor",
+            WasiAdapter::Proxy => "proxy",
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum LinkerPreference {
+    WasmLd,
+    RustLld,
+}
+
+impl FromStr for LinkerPreference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wasm-ld" => Ok(LinkerPreference::WasmLd),
+            "rust-lld" => Ok(LinkerPreference::RustLld),
+            _ => bail!("unknown linker preference {s}, must be one of: wasm-ld, rust-lld"),
+        }
+    }
+}
+
+/// A target triple passed with `--target`.
+#[derive(Debug, Clone)]
+struct Target {
+    triple: String,
+    /// Whether the target's OS is a version of WASI.
+    wasi: bool,
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (arch, rest) = s.split_once('-').unwrap_or((s, ""));
+        match arch {
+            "wasm32" => {}
+            "wasm64" => {
+                bail!("target {s} isn't supported since there is no bundled 64-bit WASI adapter")
+            }
+            _ => bail!("unknown target {s}, must be a `wasm32` target such as wasm32-wasip2"),
+        }
+        // The OS is the second component of `wasm32-wasip2` but the third of
+        // `wasm32-unknown-wasi`, so look for WASI anywhere after the arch.
+        let wasi = match rest.find("wasi") {
+            Some(i) => &rest[i..],
+            None => {
+                return Ok(Target {
+                    triple: s.to_string(),
+                    wasi: false,
+                })
+            }
+        };
+        match wasi {
+            "wasi" | "wasip1" | "wasip2" => Ok(Target {
+                triple: s.to_string(),
+                wasi: true,
+            }),
+            _ => bail!(
+                "target {s} isn't supported since there is no bundled WASI adapter for \
+                 `{wasi}`, must be one of: wasi, wasip1, wasip2"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum DepFileFormat {
+    Make,
+    Json,
+}
+
+impl FromStr for DepFileFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "make" => Ok(DepFileFormat::Make),
+            "json" => Ok(DepFileFormat::Json),
+            _ => bail!("unknown dep file format {s}, must be one of: make, json"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum OnUnresolved {
+    Import,
+    Error,
+}
+
+impl FromStr for OnUnresolved {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "import" => Ok(OnUnresolved::Import),
+            "error" => Ok(OnUnresolved::Error),
+            _ => bail!("unknown unresolved symbol behavior {s}, must be one of: import, error"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ProxyImports {
+    Permissive,
+    Strict,
+}
+
+impl FromStr for ProxyImports {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "permissive" => Ok(ProxyImports::Permissive),
+            "strict" => Ok(ProxyImports::Strict),
+            _ => bail!("unknown proxy imports mode {s}, must be one of: permissive, strict"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Intermediate {
+    Memory,
+    File,
+}
+
+impl FromStr for Intermediate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(Intermediate::Memory),
+            "file" => Ok(Intermediate::File),
+            _ => bail!("unknown intermediate strategy {s}, must be one of: memory, file"),
+        }
+    }
+}
+
+pub fn main() {
+    let err = match run() {
+        Ok(()) => return,
+        Err(e) => e,
+    };
+    if let Some(err) = err.downcast_ref::<clap::Error>() {
+        err.exit();
+    }
+    // The flag is checked directly since parsing may be what failed.
+    let github_annotations = env::args_os()
+        .take_while(|a| a != "--")
+        .any(|a| a == "--github-annotations");
+    if github_annotations {
+        eprintln!("::error::{}", github_escape(&format!("{err:#}")));
+        std::process::exit(1);
+    }
+    eprintln!("error: {err}");
+    if err.chain().len() > 1 {
+        eprintln!("\nCaused by:");
+        for (i, err) in err.chain().skip(1).enumerate() {
+            eprintln!("{i:>5}: {}", err.to_string().replace("\n", "\n       "));
+        }
+    }
+
+    std::process::exit(1);
+}
+
+/// Escapes `msg` for use in a GitHub Actions workflow command.
+fn github_escape(msg: &str) -> String {
+    msg.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn run() -> Result<()> {
+    let mut args = env::args_os().collect::<Vec<_>>();
+    if args.get(1).is_some_and(|a| a == "extract-core") {
+        args.remove(1);
+        return ExtractCoreArgs::try_parse_from(args)?.run();
+    }
+    let topic = args
+        .iter()
+        .skip(1)
+        .take_while(|a| *a != "--")
+        .find_map(|a| a.to_str()?.strip_prefix("--help="));
+    if let Some(topic) = topic {
+        return print_help_topic(topic);
+    }
+    App::parse(args)?.run()
+}
+
+/// Focused help printed with `--help=TOPIC`, as pairs of topic and text.
+const HELP_TOPICS: &[(&str, &str)] = &[
+    (
+        "adapters",
+        "\
+Adapters are core wasm modules which satisfy imports of the core module
+produced by `wasm-ld`, most commonly translating WASI preview1 imports to
+WASI preview2 imports.
+
+  --wasi-adapter=command|reactor|proxy|none
+      Select the built-in WASI adapter, inferred from `_start` by default.
+  --is-command, --is-reactor
+      Shorthands for `--wasi-adapter=command` and `--wasi-adapter=reactor`.
+  --adapt [NAME=]MODULE
+      Satisfy imports from `NAME` with the adapter in `MODULE`.
+  --adapter-for MODULE=ADAPTER
+      Like `--adapt`, but checks that every import from `MODULE` is provided.
+  --no-adapter-for=MODULE
+      Import the functions of `MODULE` from the component instead.
+  --preview1-aliases=LIST
+      Also satisfy imports from these modules with the built-in adapter.
+  --print-adapter=command|reactor|proxy
+      Print a built-in adapter and exit.
+
+Example:
+  wasm-component-ld --adapt my_module=adapter.wasm main.o -o main.wasm
+",
+    ),
+    (
+        "wit",
+        "\
+The WIT world of the component comes from component type metadata embedded in
+the input objects, merged with any WIT passed on the command line.
+
+  --component-type=PATH
+      Merge the world of the WIT file, directory, or binary package at `PATH`.
+  --component-type-override=PATH
+      Like `--component-type`, replacing conflicting items.
+  --world=NAME
+      Select the world when a WIT package has more than one.
+  --check-component-types
+      Only merge the `--component-type` inputs and exit.
+  --trace-wit-merge
+      Print each step of merging `--component-type` inputs.
+  --print-wit-packages
+      Print the WIT packages used after merging.
+
+Example:
+  wasm-component-ld --component-type=wit/ main.o -o main.wasm
+",
+    ),
+    (
+        "debugging-links",
+        "\
+These flags help to understand what happens during a link.
+
+  --verbose
+      Print the `wasm-ld` command and the world of the component.
+  --print-core-imports
+      Print the imports of the core module produced by `wasm-ld`.
+  --print-memory-config
+      Print the memory settings of the core module.
+  --artifacts-prefix=PREFIX
+      Write the core module, component, and WIT next to each other.
+  --no-temp-cleanup-on-error
+      Keep the temporary directory of a failed link.
+  extract-core COMPONENT -o CORE
+      Extract the core modules of an existing component.
+
+Example:
+  wasm-component-ld --verbose --artifacts-prefix=build/main main.o -o main.wasm
+",
+    ),
+];
+
+/// Prints the focused help of `--help=TOPIC`.
+fn print_help_topic(topic: &str) -> Result<()> {
+    match HELP_TOPICS.iter().find(|(name, _)| *name == topic) {
+        Some((_, text)) => {
+            print!("{text}");
+            Ok(())
+        }
+        None => {
+            let names = HELP_TOPICS
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ");
+            bail!("unknown help topic {topic}, must be one of: {names}")
+        }
+    }
+}
+
+/// Extracts the core wasm modules embedded within a component.
+#[derive(clap::Parser)]
+#[clap(name = "wasm-component-ld extract-core")]
+struct ExtractCoreArgs {
+    /// The component to extract core modules from.
+    component: PathBuf,
+
+    /// Where to write the extracted core module.
+    ///
+    /// If the component contains more than one core module then each one is
+    /// written with an index suffix instead, for example `core.0.wasm`,
+    /// `core.1.wasm`, etc.
+    #[clap(short, long)]
+    output: PathBuf,
+}
+
+impl ExtractCoreArgs {
+    fn run(&self) -> Result<()> {
+        let component = std::fs::read(&self.component)
+            .with_context(|| format!("failed to read {:?}", self.component))?;
+        let modules = core_modules(&component)
+            .with_context(|| format!("failed to parse {:?}", self.component))?;
+        if modules.is_empty() {
+            bail!("no core modules found in {:?}", self.component);
+        }
+        if modules.len() == 1 {
+            return std::fs::write(&self.output, modules[0])
+                .with_context(|| format!("failed to write {:?}", self.output));
+        }
+        let stem = self.output.file_stem().unwrap_or_default().to_os_string();
+        for (i, module) in modules.iter().enumerate() {
+            let mut name = stem.clone();
+            name.push(format!(".{i}"));
+            if let Some(ext) = self.output.extension() {
+                name.push(".");
+                name.push(ext);
+            }
+            let path = self.output.with_file_name(name);
+            std::fs::write(&path, module).with_context(|| format!("failed to write {path:?}"))?;
+        }
+        Ok(())
+    }
+}
+
+impl App {
+    /// Parse the CLI arguments into an `App` to run the linker.
+    ///
+    /// This is unfortunately nontrivial because the way `wasm-ld` takes
+    /// arguments is not compatible with `clap`. Namely flags like
+    /// `--whole-archive` are positional are processed in a stateful manner.
+    /// This means that the relative ordering of flags to `wasm-ld` needs to be
+    /// preserved. Additionally there are flags like `-shared` which clap does
+    /// not support.
+    ///
+    /// To handle this the `lexopt` crate is used to perform low-level argument
+    /// parsing. That's then used to determine whether the argument is intended
+    /// for `wasm-component-ld` or `wasm-ld`, so arguments are filtered into two
+    /// lists. Using these lists the arguments to `wasm-component-ld` are then
+    /// parsed. On failure a help message is presented with all `wasm-ld`
+    /// arguments added as well.
+    ///
+    /// This means that functionally it looks like `clap` parses everything when
+    /// in fact `lexopt` is used to filter out `wasm-ld` arguments and `clap`
+    /// only parses arguments specific to `wasm-component-ld`.
+    fn parse(args: impl IntoIterator<Item = OsString>) -> Result<App> {
+        let mut args = args.into_iter().collect::<Vec<_>>();
+        let invocation = args.clone();
+
+        // First remove `-flavor wasm` in case this is invoked as a generic LLD
+        // driver. We can safely ignore that going forward.
+        if let Some([flavor, wasm]) = args.get(1..3) {
+            if flavor == "-flavor" && wasm == "wasm" {
+                args.remove(1);
+                args.remove(1);
+            }
+        }
+
+        // Everything after a literal `--` is forwarded verbatim to `wasm-ld`
+        // after all other arguments, bypassing the parsing below.
+        let passthrough = match args.iter().position(|a| a == "--") {
+            Some(i) => {
+                let rest = args.split_off(i + 1);
+                args.pop();
+                rest
+            }
+            None => Vec::new(),
+        };
+
+        let mut command = ComponentLdArgs::command();
+        let mut lld_args = Vec::new();
+        let mut objects = Vec::new();
+        let mut object_positions = Vec::new();
+        let mut component_ld_args = vec![args[0].clone()];
+        let mut shared = false;
+        let mut parser = lexopt::Parser::from_iter(args);
+
+        fn handle_lld_arg(
+            lld: &LldFlag,
+            parser: &mut lexopt::Parser,
+            lld_args: &mut Vec<OsString>,
+        ) -> Result<()> {
+            let mut arg = OsString::new();
+            match (lld.short, lld.long) {
+                (_, Some(long)) => {
+                    arg.push("--");
+                    arg.push(long);
+                }
+                (Some(short), _) => {
+                    arg.push("-");
+                    arg.push(short.encode_utf8(&mut [0; 5]));
+                }
+                (None, None) => unreachable!(),
+            }
+            match lld.value {
+                FlagValue::None => {
+                    lld_args.push(arg);
+                }
+
+                FlagValue::RequiredSpace(_) => {
+                    lld_args.push(arg);
+                    lld_args.push(parser.value()?);
+                }
+
+                FlagValue::RequiredEqual(_) => {
+                    arg.push("=");
+                    arg.push(&parser.value()?);
+                    lld_args.push(arg);
+                }
+
+                // If the value is optional then the argument must have an `=`
+                // in the argument itself.
+                FlagValue::Optional(_) => {
+                    if let Some(val) = parser.optional_value() {
+                        arg.push("=");
+                        arg.push(&val);
+                    }
+                    lld_args.push(arg);
+                }
+            }
+            Ok(())
+        }
+
+        loop {
+            if let Some(mut args) = parser.try_raw_args() {
+                if let Some(arg) = args.peek() {
+                    let flag = LLD_LONG_FLAGS_NONSTANDARD.iter().find(|(name, value)| {
+                        arg == *name
+                            || (*value
+                                && arg
+                                    .to_str()
+                                    .and_then(|a| a.strip_prefix(*name))
+                                    .is_some_and(|a| a.starts_with('=')))
+                    });
+                    if let Some((name, value)) = flag {
+                        let separate_value = *value && arg == *name;
+                        lld_args.push(arg.to_owned());
+                        if arg == "-shared" {
+                            shared = true;
+                        }
+                        args.next();
+                        if separate_value {
+                            match args.next() {
+                                Some(value) => lld_args.push(value),
+                                None => bail!("missing value for `{name}`"),
+                            }
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            match parser.next()? {
+                Some(Arg::Value(obj)) => {
+                    object_positions.push(lld_args.len());
+                    objects.push(obj.clone());
+                    lld_args.push(obj);
+                }
+                Some(Arg::Short(c)) => match LLD_FLAGS.iter().find(|f| f.short == Some(c)) {
+                    Some(lld) => {
+                        handle_lld_arg(lld, &mut parser, &mut lld_args)?;
+                    }
+                    None => {
+                        component_ld_args.push(format!("-{c}").into());
+                        if let Some(arg) =
+                            command.get_arguments().find(|a| a.get_short() == Some(c))
+                        {
+                            if arg.get_action().takes_values() {
+                                component_ld_args.push(parser.value()?);
+                            }
+                        }
+                    }
+                },
+                // Objects listed in a manifest are expanded in place so that
+                // their position relative to other `wasm-ld` flags is kept.
+                Some(Arg::Long("object-manifest")) => {
+                    let path = PathBuf::from(parser.value()?);
+                    let contents = std::fs::read_to_string(&path)
+                        .with_context(|| format!("failed to read object manifest {path:?}"))?;
+                    for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+                        let mut obj = OsString::new();
+                        if line.starts_with('-') {
+                            obj.push("./");
+                        }
+                        obj.push(line);
+                        object_positions.push(lld_args.len());
+                        objects.push(obj.clone());
+                        lld_args.push(obj);
+                    }
+                    component_ld_args.push("--object-manifest".into());
+                    component_ld_args.push(path.into());
+                }
+                Some(Arg::Long(c)) => match LLD_FLAGS.iter().find(|f| f.long == Some(c)) {
+                    Some(lld) => {
+                        handle_lld_arg(lld, &mut parser, &mut lld_args)?;
+                    }
+                    None => {
+                        component_ld_args.push(format!("--{c}").into());
+                        if let Some(arg) = command.get_arguments().find(|a| a.get_long() == Some(c))
+                        {
+                            if arg.get_action().takes_values() {
+                                component_ld_args.push(parser.value()?);
+                            }
+                        }
+                    }
+                },
+                None => break,
+            }
+        }
+
+        match command.try_get_matches_from_mut(component_ld_args.clone()) {
+            Ok(matches) => {
+                let mut component = ComponentLdArgs::from_arg_matches(&matches)?;
+                if let Some(config) = component.config.clone() {
+                    apply_config(&config, &matches, &mut component, &mut lld_args)?;
+                }
+                if let Some(order) = &component.objects_order {
+                    objects = reorder_objects(order, &mut lld_args, &object_positions)?;
+                }
+                lld_args.extend(passthrough);
+                Ok(App {
+                    component,
+                    invocation,
+                    lld_args,
+                    objects,
+                    wit_files: Vec::new(),
+                    shared,
+                    post_encode: None,
+                    toolchain_linker: None,
+                    temp_dir: None,
+                })
+            }
+            Err(_) => {
+                let err = add_wasm_ld_options(ComponentLdArgs::command())
+                    .try_get_matches_from(component_ld_args)
+                    .unwrap_err();
+                Err(err.into())
+            }
+        }
+    }
+
+    fn run(&mut self) -> Result<()> {
+        let result = self.link();
+        let temp_dir = self.temp_dir.take();
+        if let (Err(_), Some(temp_dir)) = (&result, temp_dir) {
+            if self.component.no_temp_cleanup_on_error {
+                let path = temp_dir.into_path();
+                eprintln!("keeping temporary directory {path:?}");
+            }
+        }
+        result
+    }
+
+    fn link(&mut self) -> Result<()> {
+        if self.component.check_determinism {
+            return self.check_determinism();
+        }
+        if let Some(adapter) = self.component.print_adapter {
+            return self.print_adapter(adapter);
+        }
+        if let Some(path) = &self.component.validate_only {
+            let component =
+                std::fs::read(path).with_context(|| format!("failed to read {path:?}"))?;
+            validate_component(&component).with_context(|| format!("{path:?} is not valid"))?;
+            if self.component.verbose {
+                eprintln!("{path:?} is valid");
+            }
+            return Ok(());
+        }
+        if self.component.check_component_types {
+            if self.component_type()?.is_some() {
+                eprintln!("component types were merged successfully");
+            }
+            return Ok(());
+        }
+
+        self.load_adapters()?;
+
+        if let Some(version) = self.component.format_version {
+            if version != COMPONENT_FORMAT_VERSION {
+                bail!(
+                    "component format version {version} is not supported, only \
+                     version {COMPONENT_FORMAT_VERSION} can be emitted"
+                );
+            }
+        }
+
+        let whole_archive = self
+            .lld_args
+            .iter()
+            .filter(|a| *a == "--whole-archive")
+            .count();
+        let no_whole_archive = self
+            .lld_args
+            .iter()
+            .filter(|a| *a == "--no-whole-archive")
+            .count();
+        if whole_archive > no_whole_archive {
+            self.warn("`--whole-archive` is not balanced by a `--no-whole-archive`")?;
+        }
+
+        if self.component.pre_link_check {
+            check_object_features(&self.objects)?;
+        }
+
+        if let Some(name) = &self.component.toolchain {
+            match toolchain_linker(name) {
+                Ok(path) => self.toolchain_linker = Some(path),
+                Err(e) => self.warn(&format!(
+                    "failed to find the linker of toolchain `{name}`, \
+                     searching `$PATH` instead: {e:#}"
+                ))?,
+            }
+        }
+
+        let mut cmd = self.lld();
+        let linker = cmd.get_program().to_owned();
+
+        // The core module is written to a temporary directory under a name
+        // derived from `--output` so it's recognizable in `--verbose` output
+        // and diagnostics from `wasm-ld`.
+        let temp_name = match temp_output_name(self.component.output.as_deref()) {
+            Some(name) => name,
+            None if self.component.strict_temp => bail!(
+                "cannot name the temporary core module after the output path {:?} \
+                 since it has no usable file name",
+                self.component.output.as_deref().unwrap_or(Path::new(""))
+            ),
+            None => "output.wasm".to_string(),
+        };
+        let temp_dir = self
+            .temp_dir
+            .insert(tempfile::TempDir::new().context("failed to create temp directory")?);
+        let lld_output = temp_dir.path().join(temp_name);
 
         // Shared libraries don't get wit-component run below so place the
         // output directly at the desired output location. Otherwise output to a
         // temporary location for wit-component to read and then the real output
         // is created after wit-component runs.
         if self.shared {
+            self.expand_output(WasiAdapter::None);
             cmd.arg("-o").arg(self.component.output.as_ref().unwrap());
         } else {
-            cmd.arg("-o").arg(lld_output.path());
+            cmd.arg("-o").arg(&lld_output);
+        }
+
+        if self.component.verbose {
+            eprintln!("running LLD: {cmd:?}");
+        }
+        let timeout = self.component.timeout.map(Duration::from_secs);
+        let progress = (self.component.force_progress
+            || (self.component.progress && std::io::stderr().is_terminal()))
+        .then(|| Duration::from_secs(self.component.progress_interval.max(1)));
+        let status = if timeout.is_some() || progress.is_some() {
+            let mut child = cmd
+                .spawn()
+                .with_context(|| format!("failed to spawn {linker:?}\ncommand: {cmd:?}"))?;
+            wait_child(&mut child, timeout, progress)?.with_context(|| {
+                format!(
+                    "{linker:?} did not finish within {} seconds\ncommand: {cmd:?}",
+                    self.component.timeout.unwrap()
+                )
+            })?
+        } else {
+            cmd.status()
+                .with_context(|| format!("failed to spawn {linker:?}\ncommand: {cmd:?}"))?
+        };
+        if !status.success() {
+            bail!("failed to invoke LLD: {status}");
+        }
+
+        // Skip componentization with `--shared` since that's creating a shared
+        // library that's not a component yet.
+        if self.shared {
+            return self.write_dep_file();
+        }
+
+        // With `--save-temps` `wasm-ld` writes intermediate files named after
+        // its output, which is in a temporary directory, so copy them to be
+        // next to the final output instead.
+        if self.lld_args.iter().any(|a| a == "--save-temps") {
+            self.keep_saved_temps(&lld_output)?;
+        }
+
+        let mut core_module = std::fs::read(&lld_output)
+            .with_context(|| format!("failed to read {linker:?} output"))?;
+        let emit_relocs = self.lld_args.iter().any(|a| a == "--emit-relocs");
+        let relocs = if emit_relocs {
+            reloc_sections(&core_module)?
+        } else {
+            Vec::new()
+        };
+        if self.component.print_core_imports {
+            eprintln!("core module imports:");
+            for (module, field) in core_imports(&core_module)? {
+                eprintln!("  {module}.{field}");
+            }
+        }
+        if let Some(target) = &self.component.target {
+            if uses_memory64(&core_module)? {
+                bail!(
+                    "the core module produced by {linker:?} uses a 64-bit memory, which \
+                     doesn't match `--target={}`",
+                    target.triple
+                );
+            }
+        }
+        if self.component.print_memory_config {
+            print_memory_config(&core_module)?;
+        }
+        if self.component.verify_exports {
+            self.verify_exports(&core_module)?;
+        }
+        if self.component.intermediate == Some(Intermediate::Memory) {
+            self.temp_dir
+                .take()
+                .unwrap()
+                .close()
+                .context("failed to remove temporary directory")?;
+        }
+
+        if let Some(encoding) = self.component.string_encoding {
+            if let Some(embedded) = conflicting_string_encoding(&core_module, encoding)? {
+                bail!(
+                    "`--string-encoding {encoding}` conflicts with the `{embedded}` string \
+                     encoding in the core module's component type metadata"
+                );
+            }
+        }
+
+        for (name, encoding) in self.component.adapter_encodings.iter() {
+            let Some(adapter) = self.component.adapters.iter().find(|a| a.name == *name) else {
+                bail!("`--adapt-encoding` given for unknown adapter {name:?}");
+            };
+            let embedded = conflicting_string_encoding(&adapter.wasm, *encoding)
+                .with_context(|| format!("failed to inspect adapter {name:?}"))?;
+            if let Some(embedded) = embedded {
+                bail!(
+                    "`--adapt-encoding {name}={encoding}` conflicts with the `{embedded}` \
+                     string encoding in the component type metadata of adapter {name:?}"
+                );
+            }
+        }
+
+        let ty = self.component_type()?;
+        let declares_world = ty.is_some();
+        if self.component.require_component_type
+            && ty.is_none()
+            && !has_component_type_metadata(&core_module)?
+        {
+            bail!(
+                "no component type information was found, pass `--component-type` or \
+                 link objects with embedded component type metadata"
+            );
+        }
+        if let Some(ty) = ty {
+            if self.component.print_wit_packages {
+                eprintln!("WIT packages:");
+                for (_, package) in ty.resolve.packages.iter() {
+                    eprintln!("  {}", package.name);
+                }
+            }
+            let world = &ty.resolve.worlds[ty.world];
+            if world.imports.is_empty() && world.exports.is_empty() {
+                self.warn(&format!(
+                    "component type world `{}` has no imports or exports",
+                    world.name
+                ))?;
+            }
+            wit_component::embed_component_metadata(
+                &mut core_module,
+                &ty.resolve,
+                ty.world,
+                self.component
+                    .string_encoding
+                    .unwrap_or(StringEncoding::UTF8),
+            )?;
+            self.wit_files = ty.files;
+        }
+
+        if !self.component.no_adapter_for.is_empty() {
+            for module in self.component.no_adapter_for.iter() {
+                let adapted = self.component.adapters.iter().any(|a| a.name == *module)
+                    || self
+                        .component
+                        .adapters_for
+                        .iter()
+                        .any(|a| a.name == *module)
+                    || self.component.preview1_aliases.contains(module);
+                if adapted {
+                    bail!("`--no-adapter-for={module}` conflicts with an adapter for `{module}`");
+                }
+            }
+            core_module = import_without_adapter(
+                &core_module,
+                &self.component.no_adapter_for,
+                self.component
+                    .string_encoding
+                    .unwrap_or(StringEncoding::UTF8),
+            )?;
+        }
+
+        if let Some(name) = &self.component.realloc {
+            core_module = use_realloc(&core_module, name)?;
+        }
+
+        if self.component.keep_abi_helpers && !self.component.hide_abi_helpers {
+            core_module = export_abi_helpers(
+                &core_module,
+                self.component
+                    .string_encoding
+                    .unwrap_or(StringEncoding::UTF8),
+            )?;
+        }
+
+        match self.component.on_unresolved {
+            _ if self.component.adapters.iter().any(|a| a.name == "env") => {}
+            Some(OnUnresolved::Error) => {
+                let symbols = unresolved_symbols(&core_module)?;
+                if !symbols.is_empty() {
+                    let symbols = symbols
+                        .iter()
+                        .map(|s| format!("`{s}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    bail!("the core module has unresolved symbols: {symbols}");
+                }
+            }
+            Some(OnUnresolved::Import) => {
+                core_module = import_unresolved(
+                    &core_module,
+                    self.component
+                        .string_encoding
+                        .unwrap_or(StringEncoding::UTF8),
+                )?;
+            }
+            None => {}
+        }
+
+        if self.component.component_export_all {
+            let exported;
+            (core_module, exported) = export_all(
+                &core_module,
+                self.component
+                    .string_encoding
+                    .unwrap_or(StringEncoding::UTF8),
+            )?;
+            let helpers = exported
+                .iter()
+                .filter(|name| name.starts_with("cabi-"))
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>();
+            if !helpers.is_empty() {
+                self.warn(&format!(
+                    "`--component-export-all` exports ABI helpers: {}",
+                    helpers.join(", ")
+                ))?;
+            }
+        }
+
+        if !self.component.rename_import_funcs.is_empty() {
+            core_module = rename_import_funcs(&core_module, &self.component.rename_import_funcs)?;
+        }
+
+        if self.component.verbose {
+            let (_, bindgen) = wit_component::metadata::decode(&core_module)
+                .context("failed to decode component type metadata in core module")?;
+            let world = &bindgen.resolve.worlds[bindgen.world];
+            let wit = wit_component::WitPrinter::default()
+                .print(&bindgen.resolve, &[world.package.unwrap()])
+                .context("failed to print component type world")?;
+            eprintln!("encoding component with world:\n{wit}");
+        }
+
+        if self.component.canonicalize_sections {
+            core_module = canonicalize_sections(&core_module)?;
+        }
+
+        if self.component.strip_producers_before_embed {
+            core_module = strip_producers(&core_module)?;
+        }
+
+        // If the adapter wasn't explicitly specified then inspect the output
+        // module to see if it's a command or reactor. This is skipped
+        // otherwise as it requires scanning the whole module.
+        let adapter = match self.component.wasi_adapter {
+            Some(adapter) => adapter,
+            None if self.component.is_command => WasiAdapter::Command,
+            None if self.component.is_reactor => WasiAdapter::Reactor,
+            None if self.component.target.as_ref().is_some_and(|t| !t.wasi) => WasiAdapter::None,
+            None => {
+                let adapter = if exports_start(&core_module) {
+                    if core_exports(&core_module)?
+                        .iter()
+                        .any(|e| e == "_initialize")
+                    {
+                        self.warn(
+                            "the core module exports both `_start` and `_initialize`, \
+                             using the `command` WASI adapter; pass `--wasi-adapter` \
+                             to select one explicitly",
+                        )?;
+                    }
+                    WasiAdapter::Command
+                } else {
+                    WasiAdapter::Reactor
+                };
+                if self.component.verbose {
+                    eprintln!(
+                        "detected the `{adapter}` WASI adapter from the core module's exports"
+                    );
+                }
+                adapter
+            }
+        };
+        if self.component.print_adapter_kind {
+            eprintln!("adapter kind: {adapter}");
+        }
+        self.expand_output(adapter);
+        let adapter_kind = adapter;
+        let adapter = adapter.wasm().filter(|_| {
+            !self
+                .component
+                .adapters_for
+                .iter()
+                .any(|a| a.name == "wasi_snapshot_preview1")
+                && !self
+                    .component
+                    .no_adapter_for
+                    .iter()
+                    .any(|m| m == "wasi_snapshot_preview1")
+        });
+
+        if !self.component.adapters_for.is_empty() {
+            let imports = core_imports(&core_module)?;
+            for Adapter { name, path, wasm } in self.component.adapters_for.iter() {
+                let exports = core_exports(wasm)?;
+                for (_, func) in imports.iter().filter(|(module, _)| module == name) {
+                    if !exports.contains(func) {
+                        bail!(
+                            "adapter {path:?} for `{name}` does not export `{func}`, \
+                             which the core module imports from `{name}`"
+                        );
+                    }
+                }
+            }
+        }
+
+        if self.component.sort_imports {
+            let adapters = adapter
+                .into_iter()
+                .chain(self.component.adapters.iter().map(|a| &a.wasm[..]))
+                .collect::<Vec<_>>();
+            core_module = sort_imports(&core_module, &adapters)?;
+        }
+
+        if let Some(path) = &self.component.emit_core_wit {
+            write_core_wit(path, &core_module)?;
+        }
+
+        // Functions which the world given with `--component-type` exports but
+        // the core module doesn't, to explain why encoding fails below.
+        // `wit-component` remains the source of truth for what's required.
+        let missing = if declares_world {
+            missing_world_exports(&core_module).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        let mut encoder = wit_component::ComponentEncoder::default()
+            .module(&core_module)
+            .context("failed to parse core wasm for componentization")?
+            .validate(false);
+        // Names of all adapters given to the encoder, for
+        // `--skip-adapter-validation`.
+        let mut encoded_adapters = Vec::new();
+
+        if let Some(adapter) = adapter {
+            let unexpected = core_imports(&core_module)?
+                .into_iter()
+                .map(|(module, _)| module)
+                .filter(|module| {
+                    module.starts_with("wasi_")
+                        && module != "wasi_snapshot_preview1"
+                        && !self.component.preview1_aliases.contains(module)
+                        && !self.component.adapters.iter().any(|a| a.name == *module)
+                })
+                .collect::<std::collections::BTreeSet<_>>();
+            if !unexpected.is_empty() {
+                let names = unexpected
+                    .iter()
+                    .map(|m| format!("`{m}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                bail!(
+                    "the core module imports from {names}, which the WASI adapter \
+                     does not satisfy; pass `--preview1-aliases` to inject the \
+                     adapter under these names as well"
+                );
+            }
+
+            encoder = encoder
+                .adapter("wasi_snapshot_preview1", adapter)
+                .with_context(|| {
+                    format!(
+                        "failed to inject adapter\n\n{}",
+                        describe_adapter_imports(&[("wasi_snapshot_preview1", adapter)])
+                    )
+                })?;
+            encoded_adapters.push("wasi_snapshot_preview1");
+            for alias in self.component.preview1_aliases.iter() {
+                encoder = encoder.adapter(alias, adapter).with_context(|| {
+                    format!(
+                        "failed to inject adapter as {alias:?}\n\n{}",
+                        describe_adapter_imports(&[(alias.as_str(), adapter)])
+                    )
+                })?;
+                encoded_adapters.push(alias);
+            }
+        }
+
+        for Adapter { name, wasm, .. } in self.component.adapters.iter() {
+            if adapter.is_some() && name == "wasi_snapshot_preview1" {
+                self.warn("adapter `wasi_snapshot_preview1` shadows the built-in WASI adapter")?;
+            }
+            encoder = encoder.adapter(name, wasm).with_context(|| {
+                format!(
+                    "failed to inject adapter {name:?}\n\n{}",
+                    describe_adapter_imports(&[(name.as_str(), &wasm[..])])
+                )
+            })?;
+            encoded_adapters.push(name);
+        }
+
+        let mut component = encoder.encode().with_context(|| {
+            let adapters = adapter
+                .map(|wasm| ("wasi_snapshot_preview1", wasm))
+                .into_iter()
+                .chain(
+                    self.component
+                        .preview1_aliases
+                        .iter()
+                        .filter_map(|alias| Some((alias.as_str(), adapter?))),
+                )
+                .chain(
+                    self.component
+                        .adapters
+                        .iter()
+                        .map(|a| (a.name.as_str(), &a.wasm[..])),
+                )
+                .collect::<Vec<_>>();
+            let mut msg = String::from("failed to encode component");
+            if !missing.is_empty() {
+                let missing = missing
+                    .iter()
+                    .map(|name| format!("`{name}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                msg.push_str(&format!(
+                    "\n\nthe core module doesn't export functions required by the exports of \
+                     its component type world: {missing}"
+                ));
+            }
+            if !adapters.is_empty() {
+                msg.push_str("\n\n");
+                msg.push_str(&describe_adapter_imports(&adapters));
+            }
+            msg
+        })?;
+
+        if !self.component.adapter_names.is_empty() {
+            component = rename_adapter_modules(&component, &self.component.adapter_names)?;
+        }
+
+        if !self.component.normalize_imports.is_empty() {
+            component = normalize_imports(&component, &self.component.normalize_imports)?;
+        }
+
+        if let Some(dependency) = &self.component.compose_with {
+            component = compose_with(&component, dependency)
+                .with_context(|| format!("failed to compose with {dependency:?}"))?;
+        }
+
+        if self.component.package_name.is_some()
+            || !self.component.authors.is_empty()
+            || self.component.description.is_some()
+        {
+            component = self.add_package_metadata(&component)?;
+        }
+
+        if self.component.embed_build_info {
+            self.embed_build_info(&mut component)?;
+        }
+
+        // Relocations from `--emit-relocs` refer to the core module as it was
+        // produced by `wasm-ld`, so warn if they didn't make it into the
+        // component rather than silently dropping them.
+        if emit_relocs {
+            let mut kept = Vec::new();
+            for module in core_modules(&component)? {
+                kept.extend(reloc_sections(module)?);
+            }
+            let dropped = relocs
+                .iter()
+                .filter(|name| !kept.contains(name))
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>();
+            if !dropped.is_empty() {
+                self.warn(&format!(
+                    "`--emit-relocs` was passed but the relocation sections {} were \
+                     dropped while creating the component",
+                    dropped.join(", ")
+                ))?;
+            }
+        }
+        if self.component.embed_wit {
+            embed_wit(&mut component)?;
+        }
+
+        // Validation is deferred until after the post-encode hook so that
+        // whatever the hook produces is what gets validated.
+        if let Some(hook) = &mut self.post_encode {
+            hook(&mut component).context("post-encode hook failed")?;
+        }
+        if self.component.validate_component.unwrap_or(true) {
+            if self.component.skip_adapter_validation {
+                // `wit-component` names each adapter module after the name
+                // it was given with, unless renamed with `--adapter-name`.
+                let modules = encoded_adapters
+                    .iter()
+                    .map(|name| {
+                        match self.component.adapter_names.iter().find(|(n, _)| n == name) {
+                            Some((_, label)) => label.clone(),
+                            None => format!("wit-component:adapter:{name}"),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                validate_component_except_adapters(&component, &modules)
+                    .context("failed to validate component output")?;
+            } else {
+                validate_component(&component).context("failed to validate component output")?;
+            }
+        }
+        if self.component.smoke_test {
+            smoke_test(&component).context("component would fail to instantiate")?;
+        }
+
+        let exports = component_exports(&component)?;
+        if let Some(max) = self.component.max_exports {
+            if exports.len() > max {
+                bail!(
+                    "the component has {} exports, more than the maximum of {max} \
+                     set with `--max-exports`",
+                    exports.len()
+                );
+            }
+        }
+        if let Some(max) = self.component.max_imports {
+            let imports = component_imports(&component)?;
+            if imports.len() > max {
+                bail!(
+                    "the component has {} imports, more than the maximum of {max} \
+                     set with `--max-imports`",
+                    imports.len()
+                );
+            }
+        }
+
+        if exports.is_empty() {
+            self.warn(
+                "the component has no exports, which usually means that exports \
+                 are missing from the source, the wrong crate type was used, or \
+                 `--gc-sections` removed them",
+            )?;
+        }
+
+        if self.component.proxy_imports == Some(ProxyImports::Strict) {
+            let extra = component_imports(&component)?
+                .into_iter()
+                .filter(|name| {
+                    let unversioned = name.split('@').next().unwrap();
+                    !PROXY_WORLD_IMPORTS.contains(&unversioned)
+                })
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>();
+            if !extra.is_empty() {
+                bail!(
+                    "the component has imports which aren't part of the \
+                     `wasi:http/proxy` world: {}",
+                    extra.join(", ")
+                );
+            }
+        }
+
+        if self.component.reject_imports_without_version {
+            let unversioned = component_imports(&component)?
+                .into_iter()
+                .filter(|name| !name.contains('@'))
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>();
+            if !unversioned.is_empty() {
+                bail!(
+                    "the component has imports without a version: {}",
+                    unversioned.join(", ")
+                );
+            }
+        }
+
+        if !self.component.drop_imports.is_empty() {
+            let imports = component_imports(&component)?;
+            for name in self.component.drop_imports.iter() {
+                if imports.contains(name) {
+                    bail!("cannot drop import `{name}` since it's used by the component");
+                }
+            }
+        }
+
+        if let Some(path) = &self.component.emit_host_stub {
+            write_host_stub(path, &component)?;
+        }
+
+        if let Some(path) = &self.component.sbom_out {
+            self.write_sbom(path, &component, adapter.map(|_| adapter_kind))?;
+        }
+
+        if self.component.print_world_diff {
+            self.print_world_diff(&component)?;
+        }
+
+        if self.component.list_sections {
+            list_sections(&component)?;
+        }
+
+        if self.component.adapter_trace {
+            let mut injected = Vec::new();
+            if let Some(adapter) = adapter {
+                injected.push(("wasi_snapshot_preview1", adapter));
+            }
+            for Adapter { name, wasm, .. } in self.component.adapters.iter() {
+                injected.push((name.as_str(), &wasm[..]));
+            }
+            trace_adapters(&core_module, &component, &injected)?;
+        }
+
+        write_atomically(self.component.output.as_ref().unwrap(), &component)
+            .context("failed to write output file")?;
+
+        if let Some(key) = &self.component.sign_key {
+            sign_output(key, self.component.output.as_ref().unwrap(), &component)?;
+        }
+
+        if let Some(path) = &self.component.also_emit_core {
+            std::fs::write(path, &core_module)
+                .with_context(|| format!("failed to write core module to {path:?}"))?;
+        }
+
+        if let Some(prefix) = &self.component.artifacts_prefix {
+            self.write_artifacts(
+                prefix,
+                &core_module,
+                &component,
+                adapter.map(|_| adapter_kind),
+            )?;
+        }
+
+        self.write_dep_file()
+    }
+
+    /// Writes the files requested with `--artifacts-prefix`.
+    fn write_artifacts(
+        &self,
+        prefix: &Path,
+        core_module: &[u8],
+        component: &[u8],
+        adapter: Option<WasiAdapter>,
+    ) -> Result<()> {
+        let path = |suffix: &str| {
+            let mut path = prefix.as_os_str().to_owned();
+            path.push(suffix);
+            PathBuf::from(path)
+        };
+        if let Some(parent) = prefix.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create directory {parent:?}"))?;
+        }
+
+        let core_path = path(".core.wasm");
+        std::fs::write(&core_path, core_module)
+            .with_context(|| format!("failed to write {core_path:?}"))?;
+        let component_path = path(".wasm");
+        std::fs::write(&component_path, component)
+            .with_context(|| format!("failed to write {component_path:?}"))?;
+
+        let wit = component_wit(component)?;
+        let wit_path = path(".wit");
+        std::fs::write(&wit_path, wit).with_context(|| format!("failed to write {wit_path:?}"))?;
+
+        let adapters = adapter
+            .map(|kind| format!("wasi_snapshot_preview1 ({kind})"))
+            .into_iter()
+            .chain(self.component.adapters.iter().map(|a| a.name.clone()))
+            .collect::<Vec<_>>();
+        let json = serde_json::json!({
+            "output": self.component.output.as_ref().unwrap().display().to_string(),
+            "core": core_path.display().to_string(),
+            "component": component_path.display().to_string(),
+            "wit": wit_path.display().to_string(),
+            "adapters": adapters,
+            "imports": component_imports(component)?,
+            "exports": component_exports(component)?,
+        });
+        let mut manifest = serde_json::to_string_pretty(&json)?;
+        manifest.push('\n');
+        let manifest_path = path(".json");
+        std::fs::write(&manifest_path, manifest)
+            .with_context(|| format!("failed to write {manifest_path:?}"))
+    }
+
+    /// Copies the files written by `wasm-ld --save-temps` next to
+    /// `lld_output` to be next to `--output`, keeping their suffixes.
+    fn keep_saved_temps(&self, lld_output: &Path) -> Result<()> {
+        let output = self.component.output.as_ref().unwrap();
+        let dir = lld_output.parent().unwrap();
+        let prefix = lld_output.file_name().unwrap();
+        let prefix = prefix.to_str().unwrap();
+        let entries = std::fs::read_dir(dir).with_context(|| format!("failed to read {dir:?}"))?;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(suffix) = name.to_str().and_then(|n| n.strip_prefix(prefix)) else {
+                continue;
+            };
+            if suffix.is_empty() {
+                continue;
+            }
+            let mut saved = output.as_os_str().to_owned();
+            saved.push(suffix);
+            let saved = PathBuf::from(saved);
+            std::fs::copy(entry.path(), &saved)
+                .with_context(|| format!("failed to copy `--save-temps` file to {saved:?}"))?;
+            if self.component.verbose {
+                eprintln!("saved `wasm-ld` temporary file to {saved:?}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Links twice and fails if the outputs differ, for
+    /// `--check-determinism`.
+    ///
+    /// The second link writes to a temporary directory and skips all other
+    /// files such as `--dep-file` and `--also-emit-core`. It also prints
+    /// nothing from `--verbose`, `--progress`, `--list-sections`,
+    /// `--adapter-trace`, `--trace-wit-merge`, and the `--print-*` flags, so
+    /// only warnings are reported by both links.
+    fn check_determinism(&mut self) -> Result<()> {
+        let mut second = App::parse(self.invocation.clone())?;
+        self.component.check_determinism = false;
+        self.run()?;
+        let output = self.component.output.as_ref().unwrap();
+        let first = std::fs::read(output).with_context(|| format!("failed to read {output:?}"))?;
+
+        let temp_dir = tempfile::TempDir::new().context("failed to create temp directory")?;
+        let path = temp_dir
+            .path()
+            .join(output.file_name().unwrap_or("output.wasm".as_ref()));
+        second.component.check_determinism = false;
+        second.component.output = Some(path.clone());
+        second.component.dep_file = None;
+        second.component.sbom_out = None;
+        second.component.artifacts_prefix = None;
+        second.component.emit_host_stub = None;
+        second.component.emit_core_wit = None;
+        second.component.also_emit_core = None;
+        second.component.sign_key = None;
+        second.component.verbose = false;
+        second.component.progress = false;
+        second.component.force_progress = false;
+        second.component.list_sections = false;
+        second.component.adapter_trace = false;
+        second.component.print_world_diff = false;
+        second.component.print_core_imports = false;
+        second.component.print_memory_config = false;
+        second.component.print_adapter_kind = false;
+        second.component.print_wit_packages = false;
+        second.component.trace_wit_merge = false;
+        second.post_encode = self.post_encode.take();
+        second
+            .run()
+            .context("second link for `--check-determinism` failed")?;
+        let second = std::fs::read(&path).with_context(|| format!("failed to read {path:?}"))?;
+
+        let offset = first
+            .iter()
+            .zip(&second)
+            .position(|(a, b)| a != b)
+            .or_else(|| (first.len() != second.len()).then(|| first.len().min(second.len())));
+        if let Some(offset) = offset {
+            bail!(
+                "linking is not deterministic: the outputs of two links first \
+                 differ at offset {offset:#x}"
+            );
+        }
+        Ok(())
+    }
+
+    /// Replaces the `{adapter}` and `{kind}` placeholders in `--output` with
+    /// the kind of `adapter`.
+    fn expand_output(&mut self, adapter: WasiAdapter) {
+        let Some(output) = &mut self.component.output else {
+            return;
+        };
+        if let Some(template) = output.to_str() {
+            let kind = adapter.to_string();
+            *output = template
+                .replace("{adapter}", &kind)
+                .replace("{kind}", &kind)
+                .into();
+        }
+    }
+
+    /// Writes a CycloneDX SBOM for `component` to `path`.
+    fn write_sbom(
+        &self,
+        path: &Path,
+        component: &[u8],
+        adapter: Option<WasiAdapter>,
+    ) -> Result<()> {
+        let version = env!("CARGO_PKG_VERSION");
+        let mut components = Vec::new();
+        if let Some(adapter) = adapter {
+            components.push(serde_json::json!({
+                "type": "library",
+                "name": "wasi_snapshot_preview1",
+                "version": version,
+                "description": format!("bundled `{adapter}` WASI adapter"),
+            }));
+        }
+        for adapter in self.component.adapters.iter() {
+            components.push(serde_json::json!({
+                "type": "library",
+                "name": adapter.name,
+                "description": format!("adapter from {}", adapter.path.display()),
+            }));
+        }
+
+        let (resolve, world) = match wit_component::decode(component)? {
+            wit_component::DecodedWasm::Component(resolve, world) => (resolve, world),
+            wit_component::DecodedWasm::WitPackages(..) => unreachable!(),
+        };
+        let world = &resolve.worlds[world];
+        let mut packages = std::collections::BTreeSet::new();
+        for item in world.imports.values().chain(world.exports.values()) {
+            if let wit_parser::WorldItem::Interface { id, .. } = item {
+                if let Some(package) = resolve.interfaces[*id].package {
+                    let name = &resolve.packages[package].name;
+                    packages.insert((
+                        format!("{}:{}", name.namespace, name.name),
+                        name.version.as_ref().map(|v| v.to_string()),
+                    ));
+                }
+            }
+        }
+        for (name, version) in packages {
+            components.push(serde_json::json!({
+                "type": "library",
+                "name": name,
+                "version": version,
+                "description": "WIT package",
+            }));
+        }
+
+        let output = self.component.output.as_ref().unwrap();
+        let sbom = serde_json::json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "tools": [{ "name": "wasm-component-ld", "version": version }],
+                "component": {
+                    "type": "application",
+                    "name": output.file_name().unwrap_or_default().to_string_lossy(),
+                },
+            },
+            "components": components,
+        });
+        let mut contents = serde_json::to_string_pretty(&sbom)?;
+        contents.push('\n');
+        std::fs::write(path, contents).with_context(|| format!("failed to write {path:?}"))
+    }
+
+    /// Implementation of `--print-world-diff`.
+    fn print_world_diff(&self, component: &[u8]) -> Result<()> {
+        let baseline = self.component.baseline_wit.as_ref().unwrap();
+        let mut resolve = Resolve::default();
+        let (packages, _) = push_wit_path(&mut resolve, baseline)
+            .with_context(|| format!("unable to add baseline WIT {baseline:?}"))?;
+        let package = match packages[..] {
+            [package] => package,
+            _ => bail!("baseline WIT {baseline:?} must contain exactly one package"),
+        };
+        let world = resolve.select_world(package, None)?;
+        let (before_imports, before_exports) = world_item_names(&resolve, world);
+
+        let (resolve, world) = match wit_component::decode(component)? {
+            wit_component::DecodedWasm::Component(resolve, world) => (resolve, world),
+            wit_component::DecodedWasm::WitPackages(..) => unreachable!(),
+        };
+        let (after_imports, after_exports) = world_item_names(&resolve, world);
+
+        eprintln!("world diff against {baseline:?}:");
+        let mut changed = false;
+        for (kind, before, after) in [
+            ("import", &before_imports, &after_imports),
+            ("export", &before_exports, &after_exports),
+        ] {
+            for name in after.iter().filter(|n| !before.contains(n)) {
+                eprintln!("  + {kind} {name}");
+                changed = true;
+            }
+            for name in before.iter().filter(|n| !after.contains(n)) {
+                eprintln!("  - {kind} {name}");
+                changed = true;
+            }
+        }
+        if !changed {
+            eprintln!("  (no changes)");
+        }
+        Ok(())
+    }
+
+    /// Implementation of `--print-adapter`.
+    fn print_adapter(&self, adapter: WasiAdapter) -> Result<()> {
+        let Some(wasm) = adapter.wasm() else {
+            bail!("there is no bundled `{adapter}` adapter to print");
+        };
+        let wat;
+        let bytes = if self.component.output_wat {
+            wat = wasmprinter::print_bytes(wasm)?;
+            wat.as_bytes()
+        } else {
+            wasm
+        };
+        match &self.component.output {
+            Some(path) => {
+                std::fs::write(path, bytes).with_context(|| format!("failed to write {path:?}"))
+            }
+            None => std::io::Write::write_all(&mut std::io::stdout(), bytes)
+                .context("failed to write adapter to stdout"),
+        }
+    }
+
+    /// Adds `--package-name`, `--package-version`, `--authors`, and
+    /// `--description` to the metadata of `component`.
+    fn add_package_metadata(&self, component: &[u8]) -> Result<Vec<u8>> {
+        let name = self.component.package_name.as_ref().map(|name| {
+            match &self.component.package_version {
+                Some(version) => format!("{name}@{version}"),
+                None => name.clone(),
+            }
+        });
+        let registry_metadata = (!self.component.authors.is_empty()
+            || self.component.description.is_some())
+        .then(|| {
+            let mut registry = wasm_metadata::RegistryMetadata::default();
+            if !self.component.authors.is_empty() {
+                registry.set_authors(Some(self.component.authors.clone()));
+            }
+            registry.set_description(self.component.description.clone());
+            registry
+        });
+        wasm_metadata::AddMetadata {
+            name,
+            registry_metadata,
+            ..Default::default()
+        }
+        .to_wasm(component)
+        .context("failed to add package metadata to component")
+    }
+
+    /// Appends a `wasm-component-ld-build-info` custom section to `component`.
+    fn embed_build_info(&self, component: &mut Vec<u8>) -> Result<()> {
+        use wasm_encoder::{ComponentSection, Encode};
+
+        let wasm_ld_version = self
+            .find_lld()
+            .arg("--version")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+        let mut info = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "wasm-ld-version": wasm_ld_version,
+            "args": self
+                .invocation
+                .iter()
+                .map(|a| a.to_string_lossy())
+                .collect::<Vec<_>>(),
+        });
+        if !self.component.deterministic {
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .context("system time is before the unix epoch")?;
+            info["timestamp"] = timestamp.as_secs().into();
+        }
+
+        let data = serde_json::to_vec(&info)?;
+        let section = wasm_encoder::CustomSection {
+            name: "wasm-component-ld-build-info".into(),
+            data: data.into(),
+        };
+        component.push(section.id());
+        section.encode(component);
+        Ok(())
+    }
+
+    /// Warns about each `--export=SYM` in the `wasm-ld` arguments which
+    /// `module` doesn't export, for `--verify-exports`.
+    fn verify_exports(&self, module: &[u8]) -> Result<()> {
+        let exports = core_exports(module)?;
+        for arg in self.lld_args.iter() {
+            let Some(sym) = arg.to_str().and_then(|a| a.strip_prefix("--export=")) else {
+                continue;
+            };
+            if !exports.iter().any(|e| e == sym) {
+                self.warn(&format!(
+                    "`--export={sym}` was passed to `wasm-ld` but `{sym}` isn't \
+                     exported from the core module"
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints a warning, or returns it as an error with `--fail-on-warnings`.
+    fn warn(&self, msg: &str) -> Result<()> {
+        if self.component.fail_on_warnings {
+            bail!("{msg} (warning treated as an error due to `--fail-on-warnings`)");
+        }
+        if self.component.github_annotations {
+            eprintln!("::warning::{}", github_escape(msg));
+        } else {
+            eprintln!("warning: {msg}");
+        }
+        Ok(())
+    }
+
+    /// Loads all adapters from `--adapt-dir` directories and verifies that no
+    /// two adapters have the same name.
+    fn load_adapters(&mut self) -> Result<()> {
+        for dir in self.component.adapter_dirs.iter() {
+            let mut entries = std::fs::read_dir(dir)
+                .and_then(|d| d.collect::<std::io::Result<Vec<_>>>())
+                .with_context(|| format!("failed to read adapter directory {dir:?}"))?;
+            entries.sort_by_key(|e| e.file_name());
+            for entry in entries {
+                let path = entry.path();
+                match path.extension().and_then(|s| s.to_str()) {
+                    Some("wasm") | Some("wat") => {}
+                    _ => continue,
+                }
+                let wasm = wat::parse_file(&path)
+                    .with_context(|| format!("failed to read adapter {path:?}"))?;
+                self.component.adapters.push(Adapter {
+                    name: default_adapter_name(&path).to_string(),
+                    path,
+                    wasm,
+                });
+            }
+        }
+
+        self.component
+            .adapters
+            .extend(self.component.adapters_for.iter().cloned());
+
+        let mut names = HashSet::new();
+        for adapter in self.component.adapters.iter() {
+            if !names.insert(&adapter.name) {
+                bail!("adapter {:?} was specified more than once", adapter.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses all `--component-type` inputs and merges their worlds together.
+    ///
+    /// Returns `None` if no `--component-type` was specified.
+    fn component_type(&self) -> Result<Option<ComponentType>> {
+        let mut merged = None::<ComponentType>;
+        let mut seen = Vec::new();
+        let sources = self
+            .component
+            .component_types
+            .iter()
+            .map(|path| (WitSource::Path(path), false))
+            .chain(
+                self.component
+                    .component_types_inline
+                    .iter()
+                    .map(|wit| (WitSource::Inline(wit), false)),
+            )
+            .chain(
+                self.component
+                    .component_type_overrides
+                    .iter()
+                    .map(|path| (WitSource::Path(path), true)),
+            );
+        for (wit, overrides) in sources {
+            let mut resolve = Resolve::default();
+            let (packages, files) = match wit {
+                WitSource::Path(path) => push_wit_path(&mut resolve, path),
+                WitSource::Inline(contents) => {
+                    UnresolvedPackageGroup::parse(Path::new("<inline>"), contents)
+                        .and_then(|group| resolve.append(group))
+                        .map(|packages| (packages, Vec::new()))
+                }
+            }
+            .with_context(|| format!("unable to add component type {wit}"))?;
+            let trace = self.component.trace_wit_merge;
+            if trace {
+                let names = packages
+                    .iter()
+                    .map(|id| resolve.packages[*id].name.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                eprintln!("wit merge: component type {wit} contributed packages: {names}");
+            }
+            let package = match packages[..] {
+                [package] => package,
+                _ => bail!("component type {wit} must contain exactly one package"),
+            };
+            let worlds = &resolve.packages[package].worlds;
+            let world = match &self.component.world {
+                Some(name) => Some(name.as_str()),
+                None if worlds.len() > 1 => {
+                    let names = worlds
+                        .keys()
+                        .map(|name| format!("`{name}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    bail!(
+                        "component type {wit} contains multiple worlds ({names}); \
+                         pass `--world=NAME` to select one"
+                    );
+                }
+                _ => None,
+            };
+            let world = resolve
+                .select_world(package, world)
+                .with_context(|| format!("unable to select a world from {wit}"))?;
+            if trace {
+                eprintln!(
+                    "wit merge: selected world `{}` from component type {wit}",
+                    resolve.worlds[world].name
+                );
+            }
+
+            // Passing the same WIT twice, possibly from two different
+            // directories, is common with build scripts that glob for files.
+            // Merging a world with a copy of itself fails due to duplicate
+            // items, so skip worlds which are structurally identical to one
+            // that was already seen. Note that the string encoding doesn't
+            // matter here, it's only used to compare worlds.
+            let encoded =
+                wit_component::metadata::encode(&resolve, world, StringEncoding::UTF8, None)?;
+            if seen.contains(&encoded) {
+                if trace {
+                    eprintln!(
+                        "wit merge: skipping component type {wit}, its world was already merged"
+                    );
+                }
+                continue;
+            }
+            seen.push(encoded);
+
+            match &mut merged {
+                Some(merged) => {
+                    // Merging assumes that packages with the same name are
+                    // identical, so a differing definition of an already
+                    // merged world would otherwise be silently ignored.
+                    let package_name = &resolve.packages[package].name;
+                    let world_name = &resolve.worlds[world].name;
+                    let existing = merged
+                        .resolve
+                        .package_names
+                        .get(package_name)
+                        .and_then(|id| merged.resolve.packages[*id].worlds.get(world_name));
+                    if let Some(existing) = existing {
+                        let existing = wit_component::metadata::encode(
+                            &merged.resolve,
+                            *existing,
+                            StringEncoding::UTF8,
+                            None,
+                        )?;
+                        if seen.last() != Some(&existing) {
+                            bail!(
+                                "failed to merge component type {wit}: world `{world_name}` of \
+                                 package `{package_name}` differs from the one already merged"
+                            );
+                        }
+                    }
+                    let world = merged
+                        .resolve
+                        .merge(resolve)
+                        .with_context(|| format!("failed to merge component type {wit}"))?
+                        .map_world(world, None)?;
+                    if trace {
+                        eprintln!("wit merge: merged the packages of component type {wit}");
+                    }
+                    if overrides {
+                        remove_overridden(&mut merged.resolve, world, merged.world);
+                        if trace {
+                            eprintln!(
+                                "wit merge: removed items overridden by component type {wit}"
+                            );
+                        }
+                    }
+                    if world != merged.world {
+                        merged
+                            .resolve
+                            .merge_worlds(world, merged.world)
+                            .with_context(|| {
+                                format!("failed to merge the world of component type {wit}")
+                            })?;
+                        if trace {
+                            eprintln!("wit merge: merged the world of component type {wit}");
+                        }
+                    }
+                    merged.files.extend(files);
+                }
+                None => {
+                    if trace {
+                        eprintln!("wit merge: starting with the world of component type {wit}");
+                    }
+                    merged = Some(ComponentType {
+                        resolve,
+                        world,
+                        files,
+                    })
+                }
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Writes the `--dep-file`, if one was requested, listing everything that
+    /// was read to produce the output.
+    fn write_dep_file(&self) -> Result<()> {
+        let Some(path) = &self.component.dep_file else {
+            return Ok(());
+        };
+        let output = self.component.output.as_ref().unwrap();
+        let objects = self.objects.iter().map(Path::new).collect::<Vec<_>>();
+        let manifests = self
+            .component
+            .object_manifests
+            .iter()
+            .map(|p| p.as_path())
+            .collect::<Vec<_>>();
+        let adapters = self
+            .component
+            .adapters
+            .iter()
+            .map(|a| a.path.as_path())
+            .collect::<Vec<_>>();
+        let dependencies = self
+            .component
+            .compose_with
+            .as_deref()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let wit = self
+            .wit_files
+            .iter()
+            .map(|p| p.as_path())
+            .collect::<Vec<_>>();
+
+        let contents = match self
+            .component
+            .dep_file_format
+            .unwrap_or(DepFileFormat::Make)
+        {
+            DepFileFormat::Make => {
+                let mut ret = escape_make_path(output);
+                ret.push(':');
+                for input in objects
+                    .iter()
+                    .chain(&manifests)
+                    .chain(&wit)
+                    .chain(&adapters)
+                    .chain(&dependencies)
+                {
+                    ret.push(' ');
+                    ret.push_str(&escape_make_path(input));
+                }
+                ret.push('\n');
+                ret
+            }
+            DepFileFormat::Json => {
+                let strings = |paths: &[&Path]| {
+                    paths
+                        .iter()
+                        .map(|p| p.display().to_string())
+                        .collect::<Vec<_>>()
+                };
+                let json = serde_json::json!({
+                    "output": output.display().to_string(),
+                    "inputs": {
+                        "objects": strings(&objects),
+                        "object-manifests": strings(&manifests),
+                        "wit": strings(&wit),
+                        "adapters": strings(&adapters),
+                        "compose-with": strings(&dependencies),
+                    },
+                });
+                let mut ret = serde_json::to_string_pretty(&json)?;
+                ret.push('\n');
+                ret
+            }
+        };
+        std::fs::write(path, contents).with_context(|| format!("failed to write dep file {path:?}"))
+    }
+
+    fn lld(&self) -> Command {
+        let mut lld = self.find_lld();
+        if let Some(level) = self.component.opt_level {
+            lld.arg("-O").arg(level.to_string());
+            lld.arg(format!("--lto-O={level}"));
+        }
+        lld.args(&self.lld_args);
+        if self.component.verbose {
+            lld.arg("--verbose");
+        }
+        if self.component.lld_clear_env {
+            lld.env_clear();
+        }
+        lld.envs(self.component.lld_env.iter().map(|(k, v)| (k, v)));
+        lld
+    }
+
+    fn find_lld(&self) -> Command {
+        if let Some(path) = &self.component.wasm_ld_path {
+            return Command::new(path);
+        }
+        if let Some(path) = &self.toolchain_linker {
+            let mut ret = Command::new(path);
+            ret.arg("-flavor").arg("wasm");
+            return ret;
+        }
+
+        // Search for the first of `wasm-ld` or `rust-lld` in `$PATH`, taking
+        // into account any preference of one over the other.
+        let wasm_ld = format!("{}{}", self.component.lld_name, env::consts::EXE_SUFFIX);
+        let rust_lld = format!("rust-lld{}", env::consts::EXE_SUFFIX);
+        let found = match self.component.linker_preference {
+            None => find_in_path(&[&wasm_ld, &rust_lld]),
+            Some(LinkerPreference::WasmLd) => {
+                find_in_path(&[&wasm_ld]).or_else(|| find_in_path(&[&rust_lld]))
+            }
+            Some(LinkerPreference::RustLld) => {
+                find_in_path(&[&rust_lld]).or_else(|| find_in_path(&[&wasm_ld]))
+            }
+        };
+        match found {
+            Some(path) if name_is_rust_lld(&path) => {
+                let mut ret = Command::new(path);
+                ret.arg("-flavor").arg("wasm");
+                ret
+            }
+            Some(path) => Command::new(path),
+
+            // Fall back to `wasm-ld` if the search failed to get an error
+            // message that indicates that `wasm-ld` was attempted to be found
+            // but couldn't be found.
+            None => Command::new(&self.component.lld_name),
+        }
+    }
+}
+
+/// Returns the path to `rust-lld` within the sysroot of the rustup toolchain
+/// `name`.
+fn toolchain_linker(name: &str) -> Result<PathBuf> {
+    let output = Command::new("rustc")
+        .arg(format!("+{name}"))
+        .arg("--print")
+        .arg("sysroot")
+        .output()
+        .context("failed to spawn `rustc`")?;
+    if !output.status.success() {
+        bail!(
+            "`rustc +{name} --print sysroot` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let sysroot = PathBuf::from(String::from_utf8(output.stdout)?.trim());
+    let rustlib = sysroot.join("lib").join("rustlib");
+    let rust_lld = format!("rust-lld{}", env::consts::EXE_SUFFIX);
+    let entries =
+        std::fs::read_dir(&rustlib).with_context(|| format!("failed to read {rustlib:?}"))?;
+    for entry in entries {
+        let path = entry?.path().join("bin").join(&rust_lld);
+        if path.is_file() {
+            return Ok(path);
+        }
+    }
+    bail!("no `{rust_lld}` found in {rustlib:?}")
+}
+
+/// Returns the path of the first of `names` found in `$PATH`.
+///
+/// Directories of `$PATH` are searched in order, and within each directory
+/// `names` are tested in order. A `rust-lld` which doesn't support the wasm
+/// flavor, for example due to being built without the WebAssembly target, is
+/// skipped.
+fn find_in_path(names: &[&str]) -> Option<PathBuf> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path).find_map(|entry| {
+        names
+            .iter()
+            .map(|name| entry.join(name))
+            .find(|path| path.is_file() && (!name_is_rust_lld(path) || supports_wasm_flavor(path)))
+    })
+}
+
+fn name_is_rust_lld(path: &Path) -> bool {
+    path.file_stem().is_some_and(|stem| stem == "rust-lld")
+}
+
+/// Tests whether the `rust-lld` at `path` supports `-flavor wasm`.
+fn supports_wasm_flavor(path: &Path) -> bool {
+    Command::new(path)
+        .arg("-flavor")
+        .arg("wasm")
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Removes the imports and exports of `into` which are also defined by `from`,
+/// either under the same name or as the same interface.
+fn remove_overridden(resolve: &mut Resolve, from: WorldId, into: WorldId) {
+    fn interface(item: &wit_parser::WorldItem) -> Option<wit_parser::InterfaceId> {
+        match item {
+            wit_parser::WorldItem::Interface { id, .. } => Some(*id),
+            _ => None,
+        }
+    }
+
+    let from = resolve.worlds[from].clone();
+    let into = &mut resolve.worlds[into];
+    for (items, overrides) in [
+        (&mut into.imports, &from.imports),
+        (&mut into.exports, &from.exports),
+    ] {
+        items.retain(|key, item| {
+            !overrides.contains_key(key)
+                && !overrides
+                    .values()
+                    .any(|o| interface(o).is_some_and(|id| interface(item) == Some(id)))
+        });
+    }
+}
+
+/// A source of WIT given to `--component-type` or `--component-type-inline`.
+#[derive(Copy, Clone)]
+enum WitSource<'a> {
+    Path(&'a Path),
+    Inline(&'a str),
+}
+
+impl fmt::Display for WitSource<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WitSource::Path(path) => write!(f, "{path:?}"),
+            WitSource::Inline(_) => f.write_str("given inline"),
+        }
+    }
+}
+
+/// The result of merging all `--component-type` inputs together.
+struct ComponentType {
+    resolve: Resolve,
+    world: WorldId,
+    /// All files which were read to create `resolve`.
+    files: Vec<PathBuf>,
+}
+
+/// Returns whether the core wasm `module` exports a `_start` function, meaning
+/// that it's a command rather than a reactor.
+fn exports_start(module: &[u8]) -> bool {
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        if let Ok(Payload::ExportSection(e)) = payload {
+            for export in e.into_iter().flatten() {
+                if export.name == "_start" {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Returns the core wasm `module` without its `producers` custom section.
+fn strip_producers(module: &[u8]) -> Result<Vec<u8>> {
+    let mut ret = wasm_encoder::Module::new();
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        let payload = payload?;
+        if let Payload::CustomSection(s) = &payload {
+            if s.name() == "producers" {
+                continue;
+            }
+        }
+        if let Some((id, range)) = payload.as_section() {
+            ret.section(&RawSection {
+                id,
+                data: &module[range],
+            });
+        }
+    }
+    Ok(ret.finish())
+}
+
+/// Reorders the sections of the core wasm `module` into canonical order.
+///
+/// See the documentation of `--canonicalize-sections` for the exact order.
+fn canonicalize_sections(module: &[u8]) -> Result<Vec<u8>> {
+    // Section ids of known sections in the order they must appear.
+    const ORDER: &[u8] = &[1, 2, 3, 4, 5, 13, 6, 7, 8, 9, 12, 10, 11];
+
+    let mut leading_custom = Vec::new();
+    let mut sections = Vec::new();
+    let mut trailing_custom = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        let Some((id, range)) = payload?.as_section() else {
+            continue;
+        };
+        let section = RawSection {
+            id,
+            data: &module[range],
+        };
+        if id == 0 {
+            if sections.is_empty() {
+                leading_custom.push(section);
+            } else {
+                trailing_custom.push(section);
+            }
+            continue;
+        }
+        match ORDER.iter().position(|i| *i == id) {
+            Some(position) => sections.push((position, section)),
+            None => bail!("unknown section id {id} in core wasm module"),
+        }
+    }
+    sections.sort_by_key(|(position, _)| *position);
+
+    let mut ret = wasm_encoder::Module::new();
+    for section in leading_custom
+        .iter()
+        .chain(sections.iter().map(|(_, s)| s))
+        .chain(&trailing_custom)
+    {
+        ret.section(section);
+    }
+    let ret = ret.finish();
+    wasmparser::Validator::new()
+        .validate_all(&ret)
+        .context("core wasm module is invalid after reordering sections")?;
+    Ok(ret)
+}
+
+/// Arranges for ABI helpers exported from the core wasm `module` to also be
+/// exported from the component.
+///
+/// This is done by exporting them again under a kebab-case name and embedding
+/// a world which exports functions with those names.
+fn export_abi_helpers(module: &[u8], encoding: StringEncoding) -> Result<Vec<u8>> {
+    const HELPERS: &[(&str, &str, &str)] = &[(
+        "cabi_realloc",
+        "cabi-realloc",
+        "func(old-ptr: u32, old-size: u32, align: u32, new-size: u32) -> u32",
+    )];
+
+    let mut ret = wasm_encoder::Module::new();
+    let mut wit = String::from("package wasm-component-ld:abi-helpers;\nworld abi-helpers {\n");
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        let payload = payload?;
+        if let Payload::ExportSection(s) = &payload {
+            let mut exports = wasm_encoder::ExportSection::new();
+            for export in s.clone() {
+                let export = export?;
+                let kind = match export.kind {
+                    wasmparser::ExternalKind::Func => wasm_encoder::ExportKind::Func,
+                    wasmparser::ExternalKind::Table => wasm_encoder::ExportKind::Table,
+                    wasmparser::ExternalKind::Memory => wasm_encoder::ExportKind::Memory,
+                    wasmparser::ExternalKind::Global => wasm_encoder::ExportKind::Global,
+                    wasmparser::ExternalKind::Tag => wasm_encoder::ExportKind::Tag,
+                };
+                exports.export(export.name, kind, export.index);
+                let helper = HELPERS.iter().find(|(name, ..)| *name == export.name);
+                if let Some((_, alias, ty)) = helper {
+                    if kind == wasm_encoder::ExportKind::Func {
+                        exports.export(alias, kind, export.index);
+                        wit.push_str(&format!("  export {alias}: {ty};\n"));
+                    }
+                }
+            }
+            ret.section(&exports);
+            continue;
+        }
+        if let Some((id, range)) = payload.as_section() {
+            ret.section(&RawSection {
+                id,
+                data: &module[range],
+            });
+        }
+    }
+    wit.push_str("}\n");
+    let mut ret = ret.finish();
+
+    let mut resolve = Resolve::default();
+    let group = UnresolvedPackageGroup::parse(Path::new("<abi-helpers>"), &wit)?;
+    let package = resolve.append(group)?[0];
+    let world = resolve.select_world(package, None)?;
+    wit_component::embed_component_metadata(&mut ret, &resolve, world, encoding)?;
+    Ok(ret)
+}
+
+/// Exports the function `name` of the core wasm `module` as `cabi_realloc`, for
+/// `--realloc`.
+fn use_realloc(module: &[u8], name: &str) -> Result<Vec<u8>> {
+    let types = Validator::new_with_features(WasmFeatures::all())
+        .validate_all(module)
+        .context("failed to validate core module")?;
+    let index = core_export_func(module, name)?.with_context(|| {
+        format!("realloc function `{name}` isn't exported from the core module")
+    })?;
+    let ty = types[types.core_function_at(index)].unwrap_func();
+    let i32 = wasmparser::ValType::I32;
+    if ty.params() != [i32; 4] || ty.results() != [i32] {
+        bail!("realloc function `{name}` must have the signature `(i32, i32, i32, i32) -> i32`");
+    }
+
+    let mut ret = wasm_encoder::Module::new();
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        let payload = payload?;
+        if let Payload::ExportSection(s) = &payload {
+            let mut exports = wasm_encoder::ExportSection::new();
+            for export in s.clone() {
+                let export = export?;
+                if export.name != "cabi_realloc" {
+                    exports.export(export.name, export.kind.into(), export.index);
+                }
+            }
+            exports.export("cabi_realloc", wasm_encoder::ExportKind::Func, index);
+            ret.section(&exports);
+            continue;
+        }
+        if let Some((id, range)) = payload.as_section() {
+            ret.section(&RawSection {
+                id,
+                data: &module[range],
+            });
+        }
+    }
+    Ok(ret.finish())
+}
+
+/// Returns the index of the function exported as `name` from the core wasm
+/// `module`, if any.
+fn core_export_func(module: &[u8], name: &str) -> Result<Option<u32>> {
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        if let Payload::ExportSection(s) = payload? {
+            for export in s {
+                let export = export?;
+                if export.name == name && export.kind == wasmparser::ExternalKind::Func {
+                    return Ok(Some(export.index));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the kebab-case version of the core wasm `name`, if it's valid as a
+/// WIT identifier once underscores are replaced with dashes.
+fn kebab_name(name: &str) -> Option<String> {
+    let name = name.replace('_', "-");
+    let valid = name.split('-').all(|word| {
+        word.starts_with(|c: char| c.is_ascii_lowercase())
+            && word
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    });
+    valid.then_some(name)
+}
+
+/// Returns the WIT function type with the same core wasm signature as `ty`, if
+/// it only uses numeric types.
+fn wit_signature(ty: &wasmparser::FuncType) -> Option<String> {
+    let wit_type = |ty: &wasmparser::ValType| match ty {
+        wasmparser::ValType::I32 => Some("s32"),
+        wasmparser::ValType::I64 => Some("s64"),
+        wasmparser::ValType::F32 => Some("f32"),
+        wasmparser::ValType::F64 => Some("f64"),
+        _ => None,
+    };
+    if ty.params().len() > 16 || ty.results().len() > 1 {
+        return None;
+    }
+    let params = ty
+        .params()
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| Some(format!("p{i}: {}", wit_type(ty)?)))
+        .collect::<Option<Vec<_>>>()?;
+    let mut ret = format!("func({})", params.join(", "));
+    if let Some(result) = ty.results().first() {
+        ret.push_str(" -> ");
+        ret.push_str(wit_type(result)?);
+    }
+    Some(ret)
+}
+
+/// Returns the names of functions which the core wasm `module` imports from
+/// `env`, which is where `wasm-ld` places undefined symbols.
+fn unresolved_symbols(module: &[u8]) -> Result<Vec<String>> {
+    let mut ret = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        if let Payload::ImportSection(s) = payload? {
+            for import in s {
+                let import = import?;
+                if import.module == "env" && matches!(import.ty, wasmparser::TypeRef::Func(_)) {
+                    ret.push(import.name.to_string());
+                }
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Turns each function the core wasm `module` imports from `env` into an
+/// import of the component, for `--on-unresolved=import`.
+///
+/// Each function is imported under the kebab-case version of its name and
+/// must have a signature that can be expressed in WIT.
+fn import_unresolved(module: &[u8], encoding: StringEncoding) -> Result<Vec<u8>> {
+    import_funcs(module, "unresolved", encoding, |from| {
+        (from == "env").then(|| "$root".to_string())
+    })
+}
+
+/// Turns each function the core wasm `module` imports from one of `modules`
+/// into an import of the component, for `--no-adapter-for`.
+///
+/// The functions of each module are imported from an interface named after
+/// the kebab-case version of the module's name.
+fn import_without_adapter(
+    module: &[u8],
+    modules: &[String],
+    encoding: StringEncoding,
+) -> Result<Vec<u8>> {
+    for name in modules {
+        if kebab_name(name).is_none() {
+            bail!("cannot import from `{name}` since its name can't be made a kebab-case WIT name");
+        }
+    }
+    import_funcs(module, "no-adapter", encoding, |from| {
+        modules
+            .iter()
+            .any(|m| m == from)
+            .then(|| kebab_name(from).unwrap())
+    })
+}
+
+/// Rewrites the function imports of the core wasm `module` for which
+/// `import_as` returns a name to be imports of the component.
+///
+/// Functions are imported from the interface with the returned name, or from
+/// the world itself for `$root`, under the kebab-case version of their name.
+/// The component type metadata for the imports is added to the module in a
+/// package named `wasm-component-ld:{package}`.
+fn import_funcs(
+    module: &[u8],
+    package: &str,
+    encoding: StringEncoding,
+    import_as: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<u8>> {
+    let types = Validator::new_with_features(WasmFeatures::all())
+        .validate_all(module)
+        .context("failed to validate core module")?;
+
+    let mut func_index = 0;
+    let mut ret = wasm_encoder::Module::new();
+    let mut funcs = Vec::<(String, Vec<String>)>::new();
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        let payload = payload?;
+        if let Payload::ImportSection(s) = &payload {
+            let mut imports = wasm_encoder::ImportSection::new();
+            for import in s.clone() {
+                let import = import?;
+                let ty = wasm_encoder::EntityType::try_from(import.ty)
+                    .map_err(|()| anyhow::anyhow!("unsupported import type"))?;
+                let wasmparser::TypeRef::Func(_) = import.ty else {
+                    imports.import(import.module, import.name, ty);
+                    continue;
+                };
+                let index = func_index;
+                func_index += 1;
+                let Some(interface) = import_as(import.module) else {
+                    imports.import(import.module, import.name, ty);
+                    continue;
+                };
+                let Some(name) = kebab_name(import.name) else {
+                    bail!(
+                        "cannot import `{}::{}` since its name can't be made a \
+                         kebab-case WIT name",
+                        import.module,
+                        import.name
+                    );
+                };
+                let func = types[types.core_function_at(index)].unwrap_func();
+                let Some(signature) = wit_signature(func) else {
+                    bail!(
+                        "cannot import `{}::{}` since its signature can't be \
+                         expressed in WIT",
+                        import.module,
+                        import.name
+                    );
+                };
+                imports.import(&interface, &name, ty);
+                let decl = format!("{name}: {signature};");
+                match funcs.iter_mut().find(|(i, _)| *i == interface) {
+                    Some((_, decls)) => decls.push(decl),
+                    None => funcs.push((interface, vec![decl])),
+                }
+            }
+            ret.section(&imports);
+            continue;
+        }
+        if let Some((id, range)) = payload.as_section() {
+            ret.section(&RawSection {
+                id,
+                data: &module[range],
+            });
+        }
+    }
+    let mut ret = ret.finish();
+
+    let mut wit = format!("package wasm-component-ld:{package};\nworld {package} {{\n");
+    for (interface, decls) in funcs.iter() {
+        if interface == "$root" {
+            for decl in decls {
+                wit.push_str(&format!("  import {decl}\n"));
+            }
+        } else {
+            wit.push_str(&format!("  import {interface}: interface {{\n"));
+            for decl in decls {
+                wit.push_str(&format!("    {decl}\n"));
+            }
+            wit.push_str("  }\n");
+        }
+    }
+    wit.push_str("}\n");
+
+    let mut resolve = Resolve::default();
+    let group = UnresolvedPackageGroup::parse(Path::new(&format!("<{package}>")), &wit)?;
+    let package = resolve.append(group)?[0];
+    let world = resolve.select_world(package, None)?;
+    wit_component::embed_component_metadata(&mut ret, &resolve, world, encoding)?;
+    Ok(ret)
+}
+
+/// Exports all functions of the core wasm `module` with a signature that can
+/// be expressed in WIT from the component as well.
+///
+/// Returns the new module, with additional component type metadata and
+/// kebab-case aliases for exports, along with the names of the newly exported
+/// functions.
+fn export_all(module: &[u8], encoding: StringEncoding) -> Result<(Vec<u8>, Vec<String>)> {
+    let types = Validator::new_with_features(WasmFeatures::all())
+        .validate_all(module)
+        .context("failed to validate core module")?;
+    let (_, bindgen) = wit_component::metadata::decode(module)
+        .context("failed to decode component type metadata in core module")?;
+    let (_, world_exports) = world_item_names(&bindgen.resolve, bindgen.world);
+    let mut existing = world_exports.into_iter().collect::<HashSet<_>>();
+    let core_names = core_exports(module)?;
+
+    let mut exported = Vec::new();
+    let mut ret = wasm_encoder::Module::new();
+    let mut wit = String::from("package wasm-component-ld:export-all;\nworld export-all {\n");
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        let payload = payload?;
+        if let Payload::ExportSection(s) = &payload {
+            let mut exports = wasm_encoder::ExportSection::new();
+            for export in s.clone() {
+                let export = export?;
+                exports.export(export.name, export.kind.into(), export.index);
+                if export.kind != wasmparser::ExternalKind::Func {
+                    continue;
+                }
+                let Some(name) = kebab_name(export.name) else {
+                    continue;
+                };
+                if existing.contains(&name) || (name != export.name && core_names.contains(&name)) {
+                    continue;
+                }
+                let ty = types[types.core_function_at(export.index)].unwrap_func();
+                let Some(signature) = wit_signature(ty) else {
+                    continue;
+                };
+                if name != export.name {
+                    exports.export(&name, wasm_encoder::ExportKind::Func, export.index);
+                }
+                wit.push_str(&format!("  export {name}: {signature};\n"));
+                existing.insert(name.clone());
+                exported.push(name);
+            }
+            ret.section(&exports);
+            continue;
+        }
+        if let Some((id, range)) = payload.as_section() {
+            ret.section(&RawSection {
+                id,
+                data: &module[range],
+            });
+        }
+    }
+    wit.push_str("}\n");
+    let mut ret = ret.finish();
+
+    let mut resolve = Resolve::default();
+    let group = UnresolvedPackageGroup::parse(Path::new("<export-all>"), &wit)?;
+    let package = resolve.append(group)?[0];
+    let world = resolve.select_world(package, None)?;
+    wit_component::embed_component_metadata(&mut ret, &resolve, world, encoding)?;
+    Ok((ret, exported))
+}
+
+/// Replaces the component type metadata of the core wasm `module` with a
+/// world whose imports are sorted by name.
+///
+/// The imports of `adapters` are included in the world as well since they
+/// would otherwise be appended after those of the core module.
+fn sort_imports(module: &[u8], adapters: &[&[u8]]) -> Result<Vec<u8>> {
+    use wasm_encoder::{Encode, Section};
+
+    let (mut ret, mut bindgen) = wit_component::metadata::decode(module)
+        .context("failed to decode component type metadata in core module")?;
+    let resolve = &mut bindgen.resolve;
+    for adapter in adapters {
+        let (_, adapter) = wit_component::metadata::decode(adapter)
+            .context("failed to decode component type metadata in adapter")?;
+        let remap = resolve.merge(adapter.resolve)?;
+        let world = remap.map_world(adapter.world, None)?;
+        let imports = resolve.worlds[world].imports.clone();
+        for (key, item) in imports {
+            if let wit_parser::WorldItem::Interface { .. } = item {
+                resolve.worlds[bindgen.world]
+                    .imports
+                    .entry(key)
+                    .or_insert(item);
+            }
+        }
+    }
+
+    // Interfaces are sorted by name, except that an interface must come
+    // after the interfaces it depends on. Other imports, such as functions,
+    // keep their relative order after all interfaces.
+    let mut imports = std::mem::take(&mut resolve.worlds[bindgen.world].imports);
+    let mut sorted = Vec::new();
+    loop {
+        let interfaces = imports.iter().filter_map(|(key, item)| match item {
+            wit_parser::WorldItem::Interface { id, .. } => Some((key, *id)),
+            _ => None,
+        });
+        let next = interfaces
+            .filter(|(_, id)| {
+                resolve.interface_direct_deps(*id).all(|dep| {
+                    !imports.values().any(
+                        |i| matches!(i, wit_parser::WorldItem::Interface { id, .. } if *id == dep),
+                    )
+                })
+            })
+            .map(|(key, _)| key)
+            .min_by_key(|key| resolve.name_world_key(key));
+        let Some(next) = next.cloned() else {
+            break;
+        };
+        let item = imports.shift_remove(&next).unwrap();
+        sorted.push((next, item));
+    }
+    sorted.extend(imports);
+    resolve.worlds[bindgen.world].imports = sorted.into_iter().collect();
+
+    let metadata = &bindgen.metadata;
+    let encoding = metadata
+        .import_encodings
+        .values()
+        .chain(metadata.export_encodings.values())
+        .next()
+        .copied()
+        .unwrap_or(StringEncoding::UTF8);
+    let encoded = wit_component::metadata::encode(
+        resolve,
+        bindgen.world,
+        encoding,
+        bindgen.producers.as_ref(),
+    )?;
+    let section = wasm_encoder::CustomSection {
+        name: "component-type".into(),
+        data: encoded.into(),
+    };
+    ret.push(section.id());
+    section.encode(&mut ret);
+    Ok(ret)
+}
+
+/// Rewrites imports of the core wasm `module` according to `renames`, where
+/// each entry maps an `interface#function` import to another.
+fn rename_import_funcs(module: &[u8], renames: &[(String, String)]) -> Result<Vec<u8>> {
+    let (_, bindgen) = wit_component::metadata::decode(module)
+        .context("failed to decode component type metadata in core module")?;
+    let mut map = Vec::new();
+    for (from, to) in renames {
+        let (from_module, from_func) = find_import_func(&bindgen, from)?;
+        let (to_module, to_func) = find_import_func(&bindgen, to)?;
+        let variant = wit_parser::abi::AbiVariant::GuestImport;
+        if bindgen.resolve.wasm_signature(variant, from_func)
+            != bindgen.resolve.wasm_signature(variant, to_func)
+        {
+            bail!("cannot rename import `{from}` to `{to}` since their signatures differ");
         }
+        map.push((
+            (from_module, from_func.name.as_str()),
+            (to_module, to_func.name.as_str()),
+            from,
+        ));
+    }
 
-        if self.component.verbose {
-            eprintln!("running LLD: {cmd:?}");
+    let mut renamed = HashSet::new();
+    let mut ret = wasm_encoder::Module::new();
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        let payload = payload?;
+        if let Payload::ImportSection(s) = &payload {
+            let mut imports = wasm_encoder::ImportSection::new();
+            for import in s.clone() {
+                let import = import?;
+                let ty = wasm_encoder::EntityType::try_from(import.ty)
+                    .map_err(|()| anyhow::anyhow!("unsupported import type"))?;
+                let rename = map
+                    .iter()
+                    .find(|((m, f), ..)| *m == import.module && *f == import.name);
+                match rename {
+                    Some((_, (module, name), from)) => {
+                        imports.import(module, name, ty);
+                        renamed.insert(from.as_str());
+                    }
+                    None => {
+                        imports.import(import.module, import.name, ty);
+                    }
+                }
+            }
+            ret.section(&imports);
+            continue;
         }
-        let status = cmd
-            .status()
-            .with_context(|| format!("failed to spawn {linker:?}"))?;
-        if !status.success() {
-            bail!("failed to invoke LLD: {status}");
+        if let Some((id, range)) = payload.as_section() {
+            ret.section(&RawSection {
+                id,
+                data: &module[range],
+            });
+        }
+    }
+    for (from, _) in renames {
+        if !renamed.contains(from.as_str()) {
+            bail!("cannot rename import `{from}` since the core module doesn't import it");
         }
+    }
+    Ok(ret.finish())
+}
 
-        // Skip componentization with `--shared` since that's creating a shared
-        // library that's not a component yet.
-        if self.shared {
-            return Ok(());
+/// Finds the imported function `name`, written as `interface#function`, in
+/// the world of `bindgen`, returning the interface's import name and the
+/// function.
+fn find_import_func<'a>(
+    bindgen: &'a wit_component::metadata::Bindgen,
+    name: &str,
+) -> Result<(String, &'a wit_parser::Function)> {
+    let Some((interface, func)) = name.split_once('#') else {
+        bail!("expected `interface#function`, found `{name}`");
+    };
+    let resolve = &bindgen.resolve;
+    for (key, item) in resolve.worlds[bindgen.world].imports.iter() {
+        let wit_parser::WorldItem::Interface { id, .. } = item else {
+            continue;
+        };
+        let key = resolve.name_world_key(key);
+        if key != interface {
+            continue;
         }
+        if let Some(func) = resolve.interfaces[*id].functions.get(func) {
+            return Ok((key, func));
+        }
+    }
+    bail!("no imported function `{name}` found in the component type world")
+}
 
-        let reactor_adapter = include_bytes!("wasi_snapshot_preview1.reactor.wasm");
-        let command_adapter = include_bytes!("wasi_snapshot_preview1.command.wasm");
-        let proxy_adapter = include_bytes!("wasi_snapshot_preview1.proxy.wasm");
-        let core_module = std::fs::read(lld_output.path())
-            .with_context(|| format!("failed to read {linker:?} output"))?;
+/// Returns the file name to use for the temporary core wasm output of
+/// `wasm-ld` given the final `output` path.
+///
+/// This preserves the file name of `output` where possible, replacing
+/// characters which may not be valid in a file name. Long names are
+/// truncated. Returns `None` if `output` has no usable file name, for example
+/// if it's missing, isn't valid UTF-8, or is `..`.
+fn temp_output_name(output: Option<&Path>) -> Option<String> {
+    const MAX_LEN: usize = 128;
+
+    let name = output.and_then(|p| p.file_name())?.to_str()?;
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '\0' => '_',
+            c => c,
+        })
+        .collect::<String>();
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    if name.len() <= MAX_LEN {
+        return Some(name);
+    }
+    let mut end = MAX_LEN;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(name[..end].to_string())
+}
+
+/// Escapes `path` for use as a target or prerequisite in a Makefile.
+fn escape_make_path(path: &Path) -> String {
+    let mut ret = String::new();
+    for c in path.display().to_string().chars() {
+        match c {
+            ' ' | '#' => {
+                ret.push('\\');
+                ret.push(c);
+            }
+            '$' => ret.push_str("$$"),
+            _ => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// Prints, for each of `adapters`, which imports of `core_module` it
+/// satisfied and which of its own imports are still imports of the final
+/// `component`.
+fn trace_adapters(core_module: &[u8], component: &[u8], adapters: &[(&str, &[u8])]) -> Result<()> {
+    let module_imports = core_imports(core_module)?;
+    let component_imports = component_imports(component)?;
+
+    for (name, adapter) in adapters {
+        eprintln!("adapter {name:?}:");
+
+        let satisfied = module_imports
+            .iter()
+            .filter(|(module, _)| module == name)
+            .collect::<Vec<_>>();
+        eprintln!("  satisfies {} core module import(s)", satisfied.len());
+        for (module, field) in satisfied {
+            eprintln!("    {module}::{field}");
+        }
+
+        let mut remaining = Vec::new();
+        for (module, _) in core_imports(adapter)? {
+            if component_imports.contains(&module) && !remaining.contains(&module) {
+                remaining.push(module);
+            }
+        }
+        eprintln!("  leaves {} component import(s)", remaining.len());
+        for module in remaining {
+            eprintln!("    {module}");
+        }
+    }
+    Ok(())
+}
+
+/// Describes what each of `adapters`, given as pairs of name and wasm, needs
+/// from the host, for errors when encoding the component fails.
+///
+/// Imports from the core module itself and of its memory are omitted.
+fn describe_adapter_imports(adapters: &[(&str, &[u8])]) -> String {
+    let mut ret = String::new();
+    for (name, wasm) in adapters {
+        ret.push_str(&format!("adapter `{name}` imports:\n"));
+        let imports = match core_imports(wasm) {
+            Ok(imports) => imports,
+            Err(e) => {
+                ret.push_str(&format!("  (failed to read imports: {e})\n"));
+                continue;
+            }
+        };
+        let mut modules = std::collections::BTreeMap::<_, Vec<_>>::new();
+        for (module, field) in imports {
+            if module != "__main_module__" && module != "env" {
+                modules.entry(module).or_default().push(field);
+            }
+        }
+        if modules.is_empty() {
+            ret.push_str("  (nothing from the host)\n");
+        }
+        for (module, fields) in modules {
+            ret.push_str(&format!("  {module}: {}\n", fields.join(", ")));
+        }
+    }
+    ret.pop();
+    ret
+}
+
+/// Returns the `(module, field)` pairs of all imports of the core wasm module
+/// `wasm`.
+fn core_imports(wasm: &[u8]) -> Result<Vec<(String, String)>> {
+    let mut ret = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        if let Payload::ImportSection(s) = payload? {
+            for import in s {
+                let import = import?;
+                ret.push((import.module.to_string(), import.name.to_string()));
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Adds the WIT at `path` to `resolve`, like `Resolve::push_path`, except that
+/// gzip-compressed files are transparently decompressed and binary WIT
+/// packages are decoded.
+///
+/// Compressed files are detected by either a `.gz` extension or the gzip
+/// magic bytes, and binary WIT packages by the WebAssembly magic bytes.
+fn push_wit_path(resolve: &mut Resolve, path: &Path) -> Result<(Vec<PackageId>, Vec<PathBuf>)> {
+    if path.is_file() {
+        let bytes = std::fs::read(path).with_context(|| format!("failed to read {path:?}"))?;
+        let gzipped =
+            path.extension().is_some_and(|e| e == "gz") || bytes.starts_with(&[0x1f, 0x8b]);
+        if gzipped {
+            let mut contents = String::new();
+            std::io::Read::read_to_string(
+                &mut flate2::read::GzDecoder::new(&bytes[..]),
+                &mut contents,
+            )
+            .with_context(|| format!("failed to decompress {path:?}"))?;
+            let group = UnresolvedPackageGroup::parse(path, &contents)?;
+            let packages = resolve.append(group)?;
+            return Ok((packages, vec![path.to_path_buf()]));
+        }
+        if bytes.starts_with(b"\0asm") {
+            if !wasmparser::Parser::is_component(&bytes) {
+                bail!("expected a WIT file but {path:?} is a WebAssembly binary");
+            }
+            let decoded = wit_component::decode(&bytes)
+                .with_context(|| format!("failed to decode WIT package {path:?}"))?;
+            let (decoded, packages) = match decoded {
+                wit_component::DecodedWasm::WitPackages(resolve, packages) => (resolve, packages),
+                wit_component::DecodedWasm::Component(..) => {
+                    bail!("expected a WIT package but {path:?} is a component")
+                }
+            };
+            let remap = resolve.merge(decoded)?;
+            let packages = packages.iter().map(|p| remap.packages[p.index()]).collect();
+            return Ok((packages, vec![path.to_path_buf()]));
+        }
+        if std::str::from_utf8(&bytes).is_err() {
+            bail!("expected a WIT file but {path:?} contains binary data");
+        }
+    }
+    resolve.push_path(path)
+}
+
+/// Validates the component `wasm` with the component model enabled.
+fn validate_component(wasm: &[u8]) -> Result<()> {
+    Validator::new_with_features(WasmFeatures::default() | WasmFeatures::COMPONENT_MODEL)
+        .validate_all(wasm)?;
+    Ok(())
+}
+
+/// Same as `validate_component`, except that the function bodies of the
+/// adapter modules named `adapters` aren't validated, for
+/// `--skip-adapter-validation`.
+fn validate_component_except_adapters(wasm: &[u8], adapters: &[String]) -> Result<()> {
+    let mut validator =
+        Validator::new_with_features(WasmFeatures::default() | WasmFeatures::COMPONENT_MODEL);
+    // Whether each module or component currently being parsed is an adapter.
+    let mut adapter = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        let payload = payload?;
+        match &payload {
+            Payload::ModuleSection {
+                unchecked_range, ..
+            } => {
+                let module = &wasm[unchecked_range.clone()];
+                let name = match wasm_metadata::Metadata::from_binary(module)? {
+                    wasm_metadata::Metadata::Module { name, .. } => name,
+                    wasm_metadata::Metadata::Component { .. } => None,
+                };
+                adapter.push(name.is_some_and(|name| adapters.contains(&name)));
+            }
+            Payload::ComponentSection { .. } => adapter.push(false),
+            Payload::End(_) => {
+                adapter.pop();
+            }
+            _ => {}
+        }
+        if let wasmparser::ValidPayload::Func(func, body) = validator.payload(&payload)? {
+            if adapter.last() != Some(&true) {
+                func.into_validator(Default::default()).validate(&body)?;
+            }
+        }
+    }
+    Ok(())
+}
 
-        // Inspect the output module to see if it's a command or reactor.
-        let mut exports_start = false;
-        for payload in wasmparser::Parser::new(0).parse_all(&core_module) {
-            match payload {
-                Ok(Payload::ExportSection(e)) => {
-                    for export in e {
-                        if let Ok(e) = export {
-                            if e.name == "_start" {
-                                exports_start = true;
-                                break;
+/// Returns the names of the imports and exports of `world`.
+fn world_item_names(resolve: &Resolve, world: WorldId) -> (Vec<String>, Vec<String>) {
+    let world = &resolve.worlds[world];
+    let imports = world.imports.iter();
+    let exports = world.exports.iter();
+    let [imports, exports] = [imports, exports].map(|items| {
+        items
+            .filter(|(_, item)| !matches!(item, wit_parser::WorldItem::Type(_)))
+            .map(|(key, _)| resolve.name_world_key(key))
+            .collect()
+    });
+    (imports, exports)
+}
+
+/// Checks that the active data and element segments of each core module in
+/// `component` are within bounds, for `--smoke-test`.
+///
+/// Only segments with constant offsets into memories and tables defined by
+/// the same module are checked, since the size of imported ones isn't known.
+fn smoke_test(component: &[u8]) -> Result<()> {
+    fn offset(expr: &wasmparser::ConstExpr<'_>) -> Result<Option<u64>> {
+        Ok(match expr.get_operators_reader().read()? {
+            wasmparser::Operator::I32Const { value } => Some(u64::from(value as u32)),
+            wasmparser::Operator::I64Const { value } => Some(value as u64),
+            _ => None,
+        })
+    }
+
+    for (i, module) in core_modules(component)?.into_iter().enumerate() {
+        // Initial sizes in bytes and elements, or `None` if imported.
+        let mut memories = Vec::new();
+        let mut tables = Vec::new();
+        for payload in wasmparser::Parser::new(0).parse_all(module) {
+            match payload? {
+                Payload::ImportSection(s) => {
+                    for import in s {
+                        match import?.ty {
+                            wasmparser::TypeRef::Memory(_) => memories.push(None),
+                            wasmparser::TypeRef::Table(_) => tables.push(None),
+                            _ => {}
+                        }
+                    }
+                }
+                Payload::MemorySection(s) => {
+                    for ty in s {
+                        let ty = ty?;
+                        let page_size = 1 << ty.page_size_log2.unwrap_or(16);
+                        memories.push(ty.initial.checked_mul(page_size));
+                    }
+                }
+                Payload::TableSection(s) => {
+                    for table in s {
+                        tables.push(Some(table?.ty.initial));
+                    }
+                }
+                Payload::DataSection(s) => {
+                    for (j, data) in s.into_iter().enumerate() {
+                        let data = data?;
+                        let wasmparser::DataKind::Active {
+                            memory_index,
+                            offset_expr,
+                        } = data.kind
+                        else {
+                            continue;
+                        };
+                        let size = memories.get(memory_index as usize).copied().flatten();
+                        if let (Some(size), Some(offset)) = (size, offset(&offset_expr)?) {
+                            if offset.saturating_add(data.data.len() as u64) > size {
+                                bail!(
+                                    "data segment {j} of core module {i} is out of bounds \
+                                     of memory {memory_index} with an initial size of \
+                                     {size} bytes"
+                                );
+                            }
+                        }
+                    }
+                }
+                Payload::ElementSection(s) => {
+                    for (j, element) in s.into_iter().enumerate() {
+                        let element = element?;
+                        let wasmparser::ElementKind::Active {
+                            table_index,
+                            offset_expr,
+                        } = element.kind
+                        else {
+                            continue;
+                        };
+                        let table_index = table_index.unwrap_or(0);
+                        let count = match &element.items {
+                            wasmparser::ElementItems::Functions(f) => f.count(),
+                            wasmparser::ElementItems::Expressions(_, e) => e.count(),
+                        };
+                        let size = tables.get(table_index as usize).copied().flatten();
+                        if let (Some(size), Some(offset)) = (size, offset(&offset_expr)?) {
+                            if offset.saturating_add(u64::from(count)) > size {
+                                bail!(
+                                    "element segment {j} of core module {i} is out of \
+                                     bounds of table {table_index} with an initial size \
+                                     of {size} elements"
+                                );
                             }
                         }
                     }
@@ -541,76 +4399,634 @@ impl App {
                 _ => {}
             }
         }
+    }
+    Ok(())
+}
 
-        let mut encoder = wit_component::ComponentEncoder::default()
-            .module(&core_module)
-            .context("failed to parse core wasm for componentization")?
-            .validate(self.component.validate_component.unwrap_or(true));
-        let adapter = self.component.wasi_adapter.unwrap_or(if exports_start {
-            WasiAdapter::Command
-        } else {
-            WasiAdapter::Reactor
-        });
-        let adapter = match adapter {
-            WasiAdapter::Command => Some(&command_adapter[..]),
-            WasiAdapter::Reactor => Some(&reactor_adapter[..]),
-            WasiAdapter::Proxy => Some(&proxy_adapter[..]),
-            WasiAdapter::None => None,
+/// Returns whether any memory defined or imported by the core wasm module
+/// `wasm` is 64-bit.
+fn uses_memory64(wasm: &[u8]) -> Result<bool> {
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(s) => {
+                for import in s {
+                    if let wasmparser::TypeRef::Memory(ty) = import?.ty {
+                        if ty.memory64 {
+                            return Ok(true);
+                        }
+                    }
+                }
+            }
+            Payload::MemorySection(s) => {
+                for ty in s {
+                    if ty?.memory64 {
+                        return Ok(true);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(false)
+}
+
+/// Prints the memory settings of the core wasm module `wasm`, for
+/// `--print-memory-config`.
+fn print_memory_config(wasm: &[u8]) -> Result<()> {
+    let mut memory = None;
+    let mut globals = Vec::new();
+    let mut names = Vec::new();
+    let mut imported_globals = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(s) => {
+                for import in s {
+                    match import?.ty {
+                        wasmparser::TypeRef::Memory(ty) => memory = memory.or(Some(ty)),
+                        wasmparser::TypeRef::Global(_) => imported_globals += 1,
+                        _ => {}
+                    }
+                }
+            }
+            Payload::MemorySection(s) => {
+                for ty in s {
+                    memory = memory.or(Some(ty?));
+                }
+            }
+            Payload::GlobalSection(s) => {
+                for global in s {
+                    let init = match global?.init_expr.get_operators_reader().read()? {
+                        wasmparser::Operator::I32Const { value } => Some(i64::from(value)),
+                        wasmparser::Operator::I64Const { value } => Some(value),
+                        _ => None,
+                    };
+                    globals.push(init);
+                }
+            }
+            Payload::CustomSection(s) => {
+                if let wasmparser::KnownCustom::Name(reader) = s.as_known() {
+                    for name in reader {
+                        if let wasmparser::Name::Global(map) = name? {
+                            for naming in map {
+                                let naming = naming?;
+                                names.push((naming.index, naming.name.to_string()));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    eprintln!("memory config:");
+    match memory {
+        Some(ty) => {
+            // The limits of a 64-bit memory don't necessarily fit in a `u64`
+            // when counted in bytes.
+            let page_size = 1u64.checked_shl(ty.page_size_log2.unwrap_or(16));
+            let size = |pages: u64| match page_size.and_then(|p| pages.checked_mul(p)) {
+                Some(bytes) => format!("{pages} pages ({bytes} bytes)"),
+                None => format!("{pages} pages (overflow)"),
+            };
+            eprintln!("  initial memory: {}", size(ty.initial));
+            match ty.maximum {
+                Some(max) => eprintln!("  maximum memory: {}", size(max)),
+                None => eprintln!("  maximum memory: none"),
+            }
+        }
+        None => eprintln!("  no linear memory"),
+    }
+    for global in ["__stack_pointer", "__heap_base"] {
+        let value = names
+            .iter()
+            .find(|(_, name)| name == global)
+            .and_then(|(index, _)| index.checked_sub(imported_globals))
+            .and_then(|index| globals.get(index as usize).copied().flatten());
+        match value {
+            Some(value) => eprintln!("  {global}: {value:#x}"),
+            None => eprintln!("  {global}: unknown"),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the core export names of functions which the component type world
+/// of `module` exports but `module` doesn't.
+///
+/// Functions exported directly from the world are expected under their own
+/// name, and those of exported interfaces as `interface#function`.
+fn missing_world_exports(module: &[u8]) -> Result<Vec<String>> {
+    let (_, bindgen) = wit_component::metadata::decode(module)
+        .context("failed to decode component type metadata in core module")?;
+    let resolve = &bindgen.resolve;
+    let exports = core_exports(module)?;
+    let mut ret = Vec::new();
+    for (key, item) in resolve.worlds[bindgen.world].exports.iter() {
+        let required = match item {
+            wit_parser::WorldItem::Function(func) => vec![func.name.clone()],
+            wit_parser::WorldItem::Interface { id, .. } => {
+                let name = resolve.name_world_key(key);
+                resolve.interfaces[*id]
+                    .functions
+                    .keys()
+                    .map(|func| format!("{name}#{func}"))
+                    .collect()
+            }
+            wit_parser::WorldItem::Type(_) => Vec::new(),
         };
+        ret.extend(required.into_iter().filter(|name| !exports.contains(name)));
+    }
+    Ok(ret)
+}
 
-        if let Some(adapter) = adapter {
-            encoder = encoder
-                .adapter("wasi_snapshot_preview1", adapter)
-                .context("failed to inject adapter")?;
+/// Writes the WIT packages of the component type metadata in the core wasm
+/// module `module` to `path`, for `--emit-core-wit`.
+fn write_core_wit(path: &Path, module: &[u8]) -> Result<()> {
+    if !has_component_type_metadata(module)? {
+        bail!("the core module has no component type metadata to write to {path:?}");
+    }
+    let (_, bindgen) = wit_component::metadata::decode(module)
+        .context("failed to decode component type metadata in core module")?;
+    // Decoding merges the worlds of all `component-type` sections into a
+    // synthesized `root:root` world, so print the packages they came from
+    // rather than that.
+    let root = bindgen.resolve.worlds[bindgen.world].package.unwrap();
+    let mut wit = String::new();
+    for (id, _) in bindgen
+        .resolve
+        .packages
+        .iter()
+        .filter(|(id, _)| *id != root)
+    {
+        if !wit.is_empty() {
+            wit.push('\n');
         }
+        wit.push_str(
+            &wit_component::WitPrinter::default()
+                .print(&bindgen.resolve, &[id])
+                .context("failed to print the core module's component type")?,
+        );
+    }
+    std::fs::write(path, wit).with_context(|| format!("failed to write {path:?}"))
+}
 
-        for (name, adapter) in self.component.adapters.iter() {
-            encoder = encoder
-                .adapter(name, adapter)
-                .with_context(|| format!("failed to inject adapter {name:?}"))?;
+/// Returns the names of the `reloc.*` custom sections of the core wasm module
+/// `wasm`.
+fn reloc_sections(wasm: &[u8]) -> Result<Vec<String>> {
+    let mut ret = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        if let Payload::CustomSection(s) = payload? {
+            if s.name().starts_with("reloc.") {
+                ret.push(s.name().to_string());
+            }
         }
+    }
+    Ok(ret)
+}
 
-        let component = encoder.encode().context("failed to encode component")?;
+/// Returns the names of the exports of the core wasm module `wasm`.
+fn core_exports(wasm: &[u8]) -> Result<Vec<String>> {
+    let mut ret = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        if let Payload::ExportSection(s) = payload? {
+            for export in s {
+                ret.push(export?.name.to_string());
+            }
+        }
+    }
+    Ok(ret)
+}
 
-        std::fs::write(self.component.output.as_ref().unwrap(), &component)
-            .context("failed to write output file")?;
+/// Writes `contents` to `path` such that `path` is never observed partially
+/// written.
+///
+/// The contents are first written to a temporary file in the same directory
+/// as `path`, so that renaming it over `path` doesn't cross filesystems. If
+/// the rename fails with `EXDEV` regardless, as on some overlay filesystems,
+/// the temporary file is copied to `path` instead. Other errors are returned
+/// as is.
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    use std::io::Write;
 
-        Ok(())
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".wasm-component-ld");
+    // Match the permissions of `std::fs::write` rather than the owner-only
+    // default of temporary files.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(0o666));
+    }
+    let mut temp = builder
+        .tempfile_in(dir)
+        .with_context(|| format!("failed to create a temporary file in {dir:?}"))?;
+    temp.write_all(contents)
+        .and_then(|()| temp.as_file().sync_all())
+        .with_context(|| format!("failed to write {:?}", temp.path()))?;
+    match temp.persist(path) {
+        Ok(_) => Ok(()),
+        Err(err) if err.error.kind() == std::io::ErrorKind::CrossesDevices => {
+            let temp = err.file.path();
+            std::fs::copy(temp, path)
+                .and_then(|_| std::fs::File::open(path)?.sync_all())
+                .with_context(|| format!("failed to copy {temp:?} to {path:?}"))
+        }
+        Err(err) => Err(err.error).with_context(|| format!("failed to write {path:?}")),
     }
+}
 
-    fn lld(&self) -> Command {
-        let mut lld = self.find_lld();
-        lld.args(&self.lld_args);
-        if self.component.verbose {
-            lld.arg("--verbose");
+/// Signs `component` with the ed25519 secret key in the file `key` and writes
+/// the signature next to `output`, for `--sign-key`.
+fn sign_output(key: &Path, output: &Path, component: &[u8]) -> Result<()> {
+    use ed25519_dalek::Signer;
+
+    let contents = std::fs::read(key).with_context(|| format!("failed to read {key:?}"))?;
+    let secret = match contents.len() {
+        32 => contents.try_into().unwrap(),
+        _ => parse_hex_key(&String::from_utf8_lossy(&contents)).with_context(|| {
+            format!("{key:?} doesn't contain a 32-byte key as raw bytes or hexadecimal")
+        })?,
+    };
+    let signature = ed25519_dalek::SigningKey::from_bytes(&secret).sign(component);
+
+    let mut path = output.as_os_str().to_owned();
+    path.push(".sig");
+    let path = PathBuf::from(path);
+    std::fs::write(&path, signature.to_bytes())
+        .with_context(|| format!("failed to write signature {path:?}"))
+}
+
+/// Parses 64 hexadecimal digits, ignoring surrounding whitespace.
+fn parse_hex_key(s: &str) -> Result<[u8; 32]> {
+    let s = s.trim();
+    if s.len() != 64 || !s.is_ascii() {
+        bail!("expected 64 hexadecimal digits");
+    }
+    let mut ret = [0; 32];
+    for (i, byte) in ret.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..][..2], 16)?;
+    }
+    Ok(ret)
+}
+
+/// Returns the WIT world of `component` in the text format.
+fn component_wit(component: &[u8]) -> Result<String> {
+    let (resolve, world) = match wit_component::decode(component)? {
+        wit_component::DecodedWasm::Component(resolve, world) => (resolve, world),
+        wit_component::DecodedWasm::WitPackages(..) => unreachable!(),
+    };
+    let package = resolve.worlds[world].package.unwrap();
+    wit_component::WitPrinter::default()
+        .print(&resolve, &[package])
+        .context("failed to print the component's world")
+}
+
+/// Appends a `wasm-component-ld-wit` custom section with the WIT world of
+/// `component` to it, for `--embed-wit`.
+fn embed_wit(component: &mut Vec<u8>) -> Result<()> {
+    use wasm_encoder::{ComponentSection, Encode};
+
+    let wit = component_wit(component)?;
+    let section = wasm_encoder::CustomSection {
+        name: "wasm-component-ld-wit".into(),
+        data: wit.as_bytes().into(),
+    };
+    component.push(section.id());
+    section.encode(component);
+    Ok(())
+}
+
+/// Writes the world of `component` without any exports to `path`, for
+/// `--emit-host-stub`.
+fn write_host_stub(path: &Path, component: &[u8]) -> Result<()> {
+    let (mut resolve, world) = match wit_component::decode(component)? {
+        wit_component::DecodedWasm::Component(resolve, world) => (resolve, world),
+        wit_component::DecodedWasm::WitPackages(..) => unreachable!(),
+    };
+    resolve.worlds[world].exports.clear();
+    let package = resolve.worlds[world].package.unwrap();
+    let wit = wit_component::WitPrinter::default()
+        .print(&resolve, &[package])
+        .context("failed to print the component's imports")?;
+    std::fs::write(path, wit).with_context(|| format!("failed to write host stub {path:?}"))
+}
+
+/// Returns the names of the top-level imports of the component `wasm`.
+///
+/// Imports of nested modules and components are skipped.
+fn component_imports(wasm: &[u8]) -> Result<Vec<String>> {
+    let mut ret = Vec::new();
+    let mut depth = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            Payload::ComponentImportSection(s) if depth == 0 => {
+                for import in s {
+                    ret.push(import?.name.0.to_string());
+                }
+            }
+            _ => {}
         }
-        lld
     }
+    Ok(ret)
+}
 
-    fn find_lld(&self) -> Command {
-        if let Some(path) = &self.component.wasm_ld_path {
-            return Command::new(path);
+/// Prints the kind and size of each top-level section of `component` to
+/// stderr.
+fn list_sections(component: &[u8]) -> Result<()> {
+    let mut depth = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(component) {
+        let payload = payload?;
+        if depth == 0 {
+            match &payload {
+                Payload::CustomSection(s) => {
+                    eprintln!("custom section {:?}: {} bytes", s.name(), s.data().len());
+                }
+                _ => {
+                    if let Some((id, range)) = payload.as_section() {
+                        let kind = match id {
+                            1 => "core module",
+                            2 => "core instance",
+                            3 => "core type",
+                            4 => "component",
+                            5 => "instance",
+                            6 => "alias",
+                            7 => "type",
+                            8 => "canonical function",
+                            9 => "start",
+                            10 => "import",
+                            11 => "export",
+                            _ => "unknown",
+                        };
+                        eprintln!("{kind} section: {} bytes", range.len());
+                    }
+                }
+            }
         }
+        match payload {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            _ => {}
+        }
+    }
+    Ok(())
+}
 
-        // Search for the first of `wasm-ld` or `rust-lld` in `$PATH`
-        let wasm_ld = format!("wasm-ld{}", env::consts::EXE_SUFFIX);
-        let rust_lld = format!("rust-lld{}", env::consts::EXE_SUFFIX);
-        for entry in env::split_paths(&env::var_os("PATH").unwrap_or_default()) {
-            if entry.join(&wasm_ld).is_file() {
-                return Command::new(wasm_ld);
+/// Returns whether the core wasm `module` has any `component-type` custom
+/// sections with component type metadata.
+fn has_component_type_metadata(module: &[u8]) -> Result<bool> {
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        if let Payload::CustomSection(s) = payload? {
+            if s.name().starts_with("component-type") {
+                return Ok(true);
             }
-            if entry.join(&rust_lld).is_file() {
-                let mut ret = Command::new(rust_lld);
-                ret.arg("-flavor").arg("wasm");
-                return ret;
+        }
+    }
+    Ok(false)
+}
+
+/// Returns a string encoding other than `encoding` used by the component type
+/// metadata already embedded in `module`, if any.
+fn conflicting_string_encoding(
+    module: &[u8],
+    encoding: StringEncoding,
+) -> Result<Option<StringEncoding>> {
+    let (_, bindgen) = wit_component::metadata::decode(module)
+        .context("failed to decode component type metadata")?;
+    let metadata = &bindgen.metadata;
+    Ok(metadata
+        .import_encodings
+        .values()
+        .chain(metadata.export_encodings.values())
+        .find(|e| **e != encoding)
+        .copied())
+}
+
+/// Renames the core modules of adapters within `component` according to
+/// `names`, pairs of adapter name and new module name.
+fn rename_adapter_modules(component: &[u8], names: &[(String, String)]) -> Result<Vec<u8>> {
+    let mut ret = wasm_encoder::Component::new();
+    let mut renamed = HashSet::new();
+    let mut depth = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(component) {
+        let payload = payload?;
+        let section = payload.as_section().filter(|_| depth == 0);
+        match payload {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            _ => {}
+        }
+        let Some((id, range)) = section else {
+            continue;
+        };
+        let data = &component[range];
+        let is_module = id == wasm_encoder::ComponentSectionId::CoreModule as u8;
+        let module_name = match is_module.then(|| wasm_metadata::Metadata::from_binary(data)) {
+            Some(Ok(wasm_metadata::Metadata::Module { name, .. })) => name,
+            _ => None,
+        };
+        let rename = names.iter().find(|(name, _)| {
+            module_name.as_deref() == Some(&format!("wit-component:adapter:{name}"))
+        });
+        match rename {
+            Some((name, label)) => {
+                let wasm = wasm_metadata::AddMetadata {
+                    name: Some(label.clone()),
+                    ..Default::default()
+                }
+                .to_wasm(data)?;
+                ret.section(&RawSection { id, data: &wasm });
+                renamed.insert(name.as_str());
+            }
+            _ => {
+                ret.section(&RawSection { id, data });
+            }
+        }
+    }
+    for (name, _) in names {
+        if !renamed.contains(name.as_str()) {
+            bail!("cannot name adapter `{name}` since no such adapter was injected");
+        }
+    }
+    Ok(ret.finish())
+}
+
+/// Composes `component` with the dependency component at `path` for
+/// `--compose-with`, satisfying the imports of `component` with exports of
+/// the same name from the dependency.
+fn compose_with(component: &[u8], path: &Path) -> Result<Vec<u8>> {
+    use wasm_compose::graph::{Component, CompositionGraph, EncodeOptions};
+
+    let bytes = std::fs::read(path).with_context(|| format!("failed to read {path:?}"))?;
+    let root = Component::from_bytes("root", component.to_vec())?;
+    let dependency = Component::from_bytes("dependency", bytes)?;
+    let satisfied = root
+        .imports()
+        .filter_map(|(import, name, _)| {
+            let (export, _, _) = dependency.export_by_name(name)?;
+            Some((import, export, name.to_string()))
+        })
+        .collect::<Vec<_>>();
+    if satisfied.is_empty() {
+        bail!("none of the imports of the component are exported by the dependency");
+    }
+
+    let mut graph = CompositionGraph::new();
+    let root = graph.add_component(root)?;
+    let dependency = graph.add_component(dependency)?;
+    let root = graph.instantiate(root)?;
+    let dependency = graph.instantiate(dependency)?;
+    for (import, export, name) in satisfied {
+        graph
+            .connect(dependency, Some(export), root, import)
+            .with_context(|| format!("failed to satisfy import `{name}`"))?;
+    }
+    graph.encode(EncodeOptions {
+        define_components: true,
+        export: Some(root),
+        validate: true,
+    })
+}
+
+/// Renames the versioned top-level imports of `component` from packages
+/// matching the `PACKAGE` of `versions` to `VERSION`, for
+/// `--normalize-imports`.
+fn normalize_imports(component: &[u8], versions: &[(String, semver::Version)]) -> Result<Vec<u8>> {
+    fn type_ref(ty: wasmparser::ComponentTypeRef) -> wasm_encoder::ComponentTypeRef {
+        use wasm_encoder::{ComponentTypeRef, ComponentValType, TypeBounds};
+        match ty {
+            wasmparser::ComponentTypeRef::Module(i) => ComponentTypeRef::Module(i),
+            wasmparser::ComponentTypeRef::Func(i) => ComponentTypeRef::Func(i),
+            wasmparser::ComponentTypeRef::Instance(i) => ComponentTypeRef::Instance(i),
+            wasmparser::ComponentTypeRef::Component(i) => ComponentTypeRef::Component(i),
+            wasmparser::ComponentTypeRef::Value(wasmparser::ComponentValType::Primitive(ty)) => {
+                ComponentTypeRef::Value(ComponentValType::Primitive(ty.into()))
+            }
+            wasmparser::ComponentTypeRef::Value(wasmparser::ComponentValType::Type(i)) => {
+                ComponentTypeRef::Value(ComponentValType::Type(i))
+            }
+            wasmparser::ComponentTypeRef::Type(wasmparser::TypeBounds::Eq(i)) => {
+                ComponentTypeRef::Type(TypeBounds::Eq(i))
+            }
+            wasmparser::ComponentTypeRef::Type(wasmparser::TypeBounds::SubResource) => {
+                ComponentTypeRef::Type(TypeBounds::SubResource)
+            }
+        }
+    }
+
+    let rename = |name: &str| -> Result<Option<String>> {
+        let Some((path, version)) = name.rsplit_once('@') else {
+            return Ok(None);
+        };
+        let package = path.split('/').next().unwrap();
+        let namespace = package.split(':').next().unwrap();
+        let Some((_, target)) = versions
+            .iter()
+            .find(|(p, _)| p == package || p == namespace)
+        else {
+            return Ok(None);
+        };
+        let version = semver::Version::parse(version)
+            .with_context(|| format!("invalid version in import `{name}`"))?;
+        let compatible = version.major == target.major
+            && (version.major != 0 || version.minor == target.minor)
+            && (version.major != 0 || version.minor != 0 || version.patch == target.patch);
+        if !compatible {
+            bail!(
+                "cannot normalize import `{name}` to version {target} since the \
+                 versions aren't semver-compatible"
+            );
+        }
+        Ok(Some(format!("{path}@{target}")))
+    };
+
+    let mut ret = wasm_encoder::Component::new();
+    let mut names = HashSet::new();
+    let mut depth = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(component) {
+        let payload = payload?;
+        let section = payload.as_section().filter(|_| depth == 0);
+        match &payload {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            Payload::ComponentImportSection(s) if depth == 0 => {
+                let mut imports = wasm_encoder::ComponentImportSection::new();
+                for import in s.clone() {
+                    let import = import?;
+                    let name = rename(import.name.0)?.unwrap_or(import.name.0.to_string());
+                    if !names.insert(name.clone()) {
+                        bail!(
+                            "cannot normalize import `{}` since the component already \
+                             imports `{name}`",
+                            import.name.0
+                        );
+                    }
+                    imports.import(&name, type_ref(import.ty));
+                }
+                ret.section(&imports);
+                continue;
+            }
+            _ => {}
+        }
+        if let Some((id, range)) = section {
+            ret.section(&RawSection {
+                id,
+                data: &component[range],
+            });
+        }
+    }
+    Ok(ret.finish())
+}
+
+/// Returns the core modules defined at the top level of the component `wasm`,
+/// in order.
+fn core_modules(wasm: &[u8]) -> Result<Vec<&[u8]>> {
+    let mut ret = Vec::new();
+    let mut depth = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::Version {
+                encoding: wasmparser::Encoding::Module,
+                ..
+            } if depth == 0 => bail!("input is a core module, not a component"),
+            Payload::ModuleSection {
+                unchecked_range, ..
+            } => {
+                if depth == 0 {
+                    ret.push(&wasm[unchecked_range]);
+                }
+                depth += 1;
             }
+            Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            _ => {}
         }
+    }
+    Ok(ret)
+}
 
-        // Fall back to `wasm-ld` if the search failed to get an error message
-        // that indicates that `wasm-ld` was attempted to be found but couldn't
-        // be found.
-        Command::new("wasm-ld")
+fn component_exports(wasm: &[u8]) -> Result<Vec<String>> {
+    let mut ret = Vec::new();
+    let mut depth = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            Payload::ComponentExportSection(s) if depth == 0 => {
+                for export in s {
+                    ret.push(export?.name.0.to_string());
+                }
+            }
+            _ => {}
+        }
     }
+    Ok(ret)
 }
 
 fn add_wasm_ld_options(mut command: clap::Command) -> clap::Command {
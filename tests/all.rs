@@ -1,13 +1,20 @@
 use std::env;
 use std::io::{Read, Write};
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use wasmparser::Payload;
 
-fn compile(args: &[&str], src: &str) -> Vec<u8> {
-    let tempdir = tempfile::TempDir::new().unwrap();
+fn component_ld() -> PathBuf {
     let mut myself = env::current_exe().unwrap();
     myself.pop(); // exe name
     myself.pop(); // 'deps'
     myself.push("wasm-component-ld");
+    myself
+}
+
+fn compile(args: &[&str], src: &str) -> Vec<u8> {
+    let tempdir = tempfile::TempDir::new().unwrap();
+    let myself = component_ld();
     let mut rustc = Command::new("rustc")
         .arg("--target")
         .arg("wasm32-wasip1")
@@ -15,7 +22,7 @@ fn compile(args: &[&str], src: &str) -> Vec<u8> {
         .arg("-o")
         .arg("-")
         .arg("-C")
-        .arg(&format!("linker={}", myself.to_str().unwrap()))
+        .arg(format!("linker={}", myself.to_str().unwrap()))
         .args(args)
         .current_dir(tempdir.path())
         .stdout(Stdio::piped())
@@ -36,10 +43,138 @@ fn compile(args: &[&str], src: &str) -> Vec<u8> {
 }
 
 fn assert_component(bytes: &[u8]) {
-    assert!(wasmparser::Parser::is_component(&bytes));
-    wasmparser::Validator::new().validate_all(&bytes).unwrap();
+    assert!(wasmparser::Parser::is_component(bytes));
+    wasmparser::Validator::new().validate_all(bytes).unwrap();
+}
+
+/// Writes an executable shell script running `body` to `dir/name`.
+#[cfg(unix)]
+fn write_script(dir: &Path, name: &str, body: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{body}")).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+/// A stand-in for `wasm-ld` which records the arguments it was invoked with
+/// and "links" by copying a fixed core wasm module to its `-o` location.
+///
+/// This is used to exercise `wasm-component-ld` itself without depending on
+/// the exact output of a real `wasm-ld`.
+#[cfg(unix)]
+struct FakeLld {
+    dir: tempfile::TempDir,
+}
+
+#[cfg(unix)]
+impl FakeLld {
+    fn new(core_wat: &str) -> FakeLld {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("core.wasm"),
+            wat::parse_str(core_wat).unwrap(),
+        )
+        .unwrap();
+        write_script(
+            dir.path(),
+            "wasm-ld",
+            r#"dir="${0%/*}"
+printf '%s\n' "$@" > "$dir/args"
+while [ $# -gt 0 ]; do
+    if [ "$1" = "-o" ]; then
+        /bin/cp "$dir/core.wasm" "$2"
+    fi
+    shift
+done
+"#,
+        );
+        FakeLld { dir }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.path().join("wasm-ld")
+    }
+
+    /// Returns the path to `name` within this linker's scratch directory.
+    fn file(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    fn output_path(&self) -> PathBuf {
+        self.file("out.wasm")
+    }
+
+    /// Installs another copy of this fake linker under `name`.
+    fn install_as(&self, name: &str) {
+        std::fs::copy(self.path(), self.file(name)).unwrap();
+    }
+
+    /// Returns a `wasm-component-ld` command which finds this linker through
+    /// `$PATH` rather than `--wasm-ld-path`.
+    fn command_via_path(&self) -> Command {
+        let mut cmd = Command::new(component_ld());
+        cmd.env("PATH", self.dir.path())
+            .arg("-o")
+            .arg(self.output_path());
+        cmd
+    }
+
+    /// Runs `wasm-component-ld` with `args` using this fake linker.
+    fn link(&self, args: &[&str]) -> Output {
+        Command::new(component_ld())
+            .arg("--wasm-ld-path")
+            .arg(self.path())
+            .args(args)
+            .arg("-o")
+            .arg(self.output_path())
+            .output()
+            .expect("failed to spawn wasm-component-ld")
+    }
+
+    /// Same as `link`, but asserts that linking succeeded and returns the
+    /// standard error of `wasm-component-ld`.
+    fn link_ok(&self, args: &[&str]) -> String {
+        let output = self.link(args);
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert!(output.status.success(), "link failed: {stderr}");
+        stderr
+    }
+
+    /// Same as `link`, but asserts that linking failed and returns the
+    /// standard error of `wasm-component-ld`.
+    fn link_err(&self, args: &[&str]) -> String {
+        let output = self.link(args);
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert!(!output.status.success(), "link unexpectedly succeeded");
+        stderr
+    }
+
+    /// Returns the arguments that this linker was last invoked with.
+    fn args(&self) -> Vec<String> {
+        std::fs::read_to_string(self.dir.path().join("args"))
+            .unwrap()
+            .lines()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn output(&self) -> Vec<u8> {
+        std::fs::read(self.output_path()).unwrap()
+    }
 }
 
+/// A core module which uses WASI and doesn't export `_start`.
+#[cfg(unix)]
+const REACTOR_WAT: &str = r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+)
+"#;
+
 #[test]
 fn empty() {
     let output = compile(&["--crate-type", "cdylib"], "");
@@ -117,3 +252,2640 @@ fn main() {
     );
     assert_component(&output);
 }
+
+#[test]
+#[cfg(unix)]
+fn adapter_trace() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let stderr = lld.link_ok(&["--adapter-trace"]);
+    assert_component(&lld.output());
+    assert!(
+        stderr.contains("adapter \"wasi_snapshot_preview1\""),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("wasi_snapshot_preview1::fd_write"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("wasi:io/streams"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn mllvm_forwarded_in_order() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--mllvm=-first", "-mllvm", "-second", "-mllvm=-third"]);
+    let args = lld.args();
+    let start = args.iter().position(|a| a == "--mllvm=-first").unwrap();
+    assert_eq!(
+        args[start..start + 4],
+        ["--mllvm=-first", "-mllvm", "-second", "-mllvm=-third"],
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn dep_file_make() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let dep_file = lld.file("out.d");
+    lld.link_ok(&["a.o", "b c.o", "--dep-file", dep_file.to_str().unwrap()]);
+    let contents = std::fs::read_to_string(&dep_file).unwrap();
+    let expected = format!("{}: a.o b\\ c.o\n", lld.output_path().display());
+    assert_eq!(contents, expected);
+}
+
+#[test]
+#[cfg(unix)]
+fn dep_file_json() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let dep_file = lld.file("out.json");
+    lld.link_ok(&[
+        "a.o",
+        "--dep-file",
+        dep_file.to_str().unwrap(),
+        "--dep-file-format=json",
+    ]);
+    let contents = std::fs::read_to_string(&dep_file).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+    assert_eq!(json["output"], lld.output_path().to_str().unwrap());
+    assert_eq!(json["inputs"]["objects"], serde_json::json!(["a.o"]));
+    assert_eq!(json["inputs"]["adapters"], serde_json::json!([]));
+}
+
+#[test]
+#[cfg(unix)]
+fn component_type_duplicate() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let wit = lld.file("dup.wit");
+    std::fs::write(
+        &wit,
+        "package test:dup;\nworld dup { import foo: func(); }\n",
+    )
+    .unwrap();
+    let wit = wit.to_str().unwrap();
+    lld.link_ok(&["--component-type", wit, "--component-type", wit]);
+    assert_component(&lld.output());
+}
+
+#[test]
+#[cfg(unix)]
+fn component_type_conflict() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let a = lld.file("a.wit");
+    let b = lld.file("b.wit");
+    std::fs::write(&a, "package test:dup;\nworld dup { import foo: func(); }\n").unwrap();
+    std::fs::write(
+        &b,
+        "package test:dup;\nworld dup { import foo: func(x: u32); }\n",
+    )
+    .unwrap();
+    let stderr = lld.link_err(&[
+        "--component-type",
+        a.to_str().unwrap(),
+        "--component-type",
+        b.to_str().unwrap(),
+    ]);
+    assert!(
+        stderr.contains("failed to merge component type"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn linker_preference() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.install_as("rust-lld");
+
+    let output = lld.command_via_path().output().unwrap();
+    assert!(output.status.success());
+    assert!(!lld.args().iter().any(|a| a == "-flavor"));
+
+    let output = lld
+        .command_via_path()
+        .arg("--linker-preference=rust-lld")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(lld.args()[..2], ["-flavor", "wasm"]);
+}
+
+#[test]
+#[cfg(unix)]
+fn rust_lld_without_wasm_flavor_skipped() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let bogus = tempfile::TempDir::new().unwrap();
+    write_script(bogus.path(), "rust-lld", "exit 1\n");
+
+    let path = env::join_paths([bogus.path(), lld.path().parent().unwrap()]).unwrap();
+    let output = lld.command_via_path().env("PATH", path).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(!lld.args().iter().any(|a| a == "-flavor"));
+}
+
+#[test]
+#[cfg(unix)]
+fn toolchain() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let sysroot = lld.file("sysroot");
+    let bin = sysroot.join("lib/rustlib/wasm32-host/bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    write_script(&bin, "rust-lld", &format!("exec {:?} \"$@\"\n", lld.path()));
+    write_script(
+        lld.dir.path(),
+        "rustc",
+        &format!("[ \"$1\" = +nightly ] || exit 1\necho {sysroot:?}\n"),
+    );
+
+    let output = lld
+        .command_via_path()
+        .arg("--toolchain=nightly")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(lld.args()[..2], ["-flavor", "wasm"]);
+
+    // Without a matching toolchain the linker in `$PATH` is used instead.
+    let output = lld
+        .command_via_path()
+        .arg("--toolchain=stable")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("searching `$PATH` instead"), "{stderr}");
+    assert!(!lld.args().iter().any(|a| a == "-flavor"));
+}
+
+#[test]
+fn spawn_failure_includes_command() {
+    let tempdir = tempfile::TempDir::new().unwrap();
+    let output = Command::new(component_ld())
+        .arg("--wasm-ld-path")
+        .arg(tempdir.path().join("nonexistent-wasm-ld"))
+        .arg("foo.o")
+        .arg("-o")
+        .arg(tempdir.path().join("out.wasm"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("failed to spawn"), "{stderr}");
+    assert!(stderr.contains("nonexistent-wasm-ld"), "{stderr}");
+    assert!(stderr.contains("\"foo.o\""), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn timeout() {
+    let tempdir = tempfile::TempDir::new().unwrap();
+    let lld = write_script(tempdir.path(), "wasm-ld", "exec sleep 60\n");
+
+    let start = std::time::Instant::now();
+    let output = Command::new(component_ld())
+        .arg("--wasm-ld-path")
+        .arg(&lld)
+        .arg("--timeout=1")
+        .arg("-o")
+        .arg(tempdir.path().join("out.wasm"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(start.elapsed() < std::time::Duration::from_secs(30));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("did not finish within 1 seconds"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn progress() {
+    let tempdir = tempfile::TempDir::new().unwrap();
+    let lld = write_script(tempdir.path(), "wasm-ld", "sleep 3\nexit 1\n");
+
+    let link = |arg: &str| {
+        let output = Command::new(component_ld())
+            .arg("--wasm-ld-path")
+            .arg(&lld)
+            .arg(arg)
+            .arg("--progress-interval=1")
+            .arg("-o")
+            .arg(tempdir.path().join("out.wasm"))
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+
+    let stderr = link("--force-progress");
+    assert!(stderr.contains("still linking (1s elapsed)"), "{stderr}");
+
+    // stderr is a pipe here, so plain `--progress` prints nothing.
+    let stderr = link("--progress");
+    assert!(!stderr.contains("still linking"), "{stderr}");
+}
+
+/// Returns the sections of each core module within `component`, as pairs of
+/// section id and custom section name.
+fn core_module_sections(component: &[u8]) -> Vec<Vec<(u8, String)>> {
+    let mut modules = Vec::new();
+    let mut current = None;
+    for payload in wasmparser::Parser::new(0).parse_all(component) {
+        let payload = payload.unwrap();
+        match &payload {
+            Payload::Version {
+                encoding: wasmparser::Encoding::Module,
+                ..
+            } => current = Some(Vec::new()),
+            Payload::End(_) => modules.extend(current.take()),
+            _ => {
+                if let (Some(sections), Some((id, _))) = (&mut current, payload.as_section()) {
+                    let name = match &payload {
+                        Payload::CustomSection(c) => c.name().to_string(),
+                        _ => String::new(),
+                    };
+                    sections.push((id, name));
+                }
+            }
+        }
+    }
+    modules
+}
+
+/// Returns the names of the top-level imports of `component`.
+fn component_imports(component: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut depth = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(component) {
+        match payload.unwrap() {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            Payload::ComponentImportSection(s) if depth == 0 => {
+                names.extend(s.into_iter().map(|i| i.unwrap().name.0.to_string()));
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Returns the names of the top-level exports of `component`.
+fn component_exports(component: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut depth = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(component) {
+        match payload.unwrap() {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            Payload::ComponentExportSection(s) if depth == 0 => {
+                names.extend(s.into_iter().map(|e| e.unwrap().name.0.to_string()));
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+#[test]
+#[cfg(unix)]
+fn canonicalize_sections() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (@custom "middle" (after import) "x")
+    (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    lld.link_ok(&["--canonicalize-sections"]);
+    let component = lld.output();
+    assert_component(&component);
+
+    let modules = core_module_sections(&component);
+    let main = modules
+        .iter()
+        .find(|m| m.iter().any(|(_, name)| name == "middle"))
+        .unwrap();
+    let middle = main.iter().position(|(_, name)| name == "middle").unwrap();
+    assert!(main[middle..].iter().all(|(id, _)| *id == 0), "{main:?}");
+}
+
+#[test]
+#[cfg(unix)]
+fn adapt_dir() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "a" "f" (func))
+    (import "b" "g" (func))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let dir = lld.file("adapters");
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(dir.join("a.wat"), r#"(module (func (export "f")))"#).unwrap();
+    std::fs::write(dir.join("b.wat"), r#"(module (func (export "g")))"#).unwrap();
+    std::fs::write(dir.join("README.md"), "not an adapter").unwrap();
+    let dir = dir.to_str().unwrap();
+
+    lld.link_ok(&["--adapt-dir", dir]);
+    assert_component(&lld.output());
+
+    let a = format!("{dir}/a.wat");
+    let stderr = lld.link_err(&["--adapt-dir", dir, "--adapt", &a]);
+    assert!(stderr.contains("specified more than once"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn explicit_adapter_skips_export_scan() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let stderr = lld.link_ok(&["--verbose"]);
+    assert!(
+        stderr.contains("detected the `reactor` WASI adapter"),
+        "{stderr}"
+    );
+
+    let stderr = lld.link_ok(&["--verbose", "--wasi-adapter=reactor"]);
+    assert!(!stderr.contains("detected the"), "{stderr}");
+    assert_component(&lld.output());
+}
+
+#[test]
+#[cfg(unix)]
+fn component_type_inline() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&[
+        "--component-type-inline",
+        "package test:inline; world w { import foo: func(); }",
+    ]);
+    assert_component(&lld.output());
+
+    let stderr = lld.link_err(&["--component-type-inline", "package test:inline; world {"]);
+    assert!(
+        stderr.contains("unable to add component type given inline"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn post_encode_hook() {
+    use wasm_encoder::{Encode, Section};
+
+    let lld = FakeLld::new(REACTOR_WAT);
+    wasm_component_ld::Linker::new([
+        "--wasm-ld-path".into(),
+        lld.path().into_os_string(),
+        "-o".into(),
+        lld.output_path().into_os_string(),
+    ])
+    .with_post_encode(Box::new(|component| {
+        let section = wasm_encoder::CustomSection {
+            name: "hook".into(),
+            data: b"hello"[..].into(),
+        };
+        component.push(section.id());
+        section.encode(component);
+        Ok(())
+    }))
+    .run()
+    .unwrap();
+
+    let output = lld.output();
+    assert_component(&output);
+    let found = wasmparser::Parser::new(0)
+        .parse_all(&output)
+        .any(|payload| match payload.unwrap() {
+            Payload::CustomSection(s) => s.name() == "hook" && s.data() == b"hello",
+            _ => false,
+        });
+    assert!(found);
+}
+
+#[test]
+#[cfg(unix)]
+fn fail_on_warnings() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let wit = "package test:empty; world empty {}";
+    let stderr = lld.link_ok(&["--component-type-inline", wit]);
+    assert!(
+        stderr.contains("warning: component type world `empty` has no imports or exports"),
+        "{stderr}"
+    );
+
+    let stderr = lld.link_err(&["--component-type-inline", wit, "--fail-on-warnings"]);
+    assert!(stderr.contains("`--fail-on-warnings`"), "{stderr}");
+
+    // `--fatal-warnings` is only for `wasm-ld` and is forwarded as-is.
+    lld.link_ok(&["--component-type-inline", wit, "--fatal-warnings"]);
+    assert!(lld.args().contains(&"--fatal-warnings".to_string()));
+}
+
+#[test]
+#[cfg(unix)]
+fn extract_core() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&[]);
+
+    let output = Command::new(component_ld())
+        .arg("extract-core")
+        .arg(lld.output_path())
+        .arg("-o")
+        .arg(lld.file("extracted.wasm"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    // The main module comes first, followed by the adapter and shims.
+    assert!(!lld.file("extracted.wasm").exists());
+    let main = std::fs::read(lld.file("extracted.0.wasm")).unwrap();
+    assert!(lld.file("extracted.1.wasm").exists());
+    wasmparser::Validator::new().validate_all(&main).unwrap();
+    let imports_fd_write = wasmparser::Parser::new(0)
+        .parse_all(&main)
+        .any(|payload| match payload.unwrap() {
+            Payload::ImportSection(s) => s.into_iter().any(|i| i.unwrap().name == "fd_write"),
+            _ => false,
+        });
+    assert!(imports_fd_write);
+}
+
+#[test]
+#[cfg(unix)]
+fn string_encoding_conflict() {
+    let lld = FakeLld::new(REACTOR_WAT);
+
+    let mut resolve = wit_parser::Resolve::default();
+    let wit = "package test:enc; world w { import f: func(s: string); }";
+    let group = wit_parser::UnresolvedPackageGroup::parse("enc.wit".as_ref(), wit).unwrap();
+    let pkg = resolve.append(group).unwrap()[0];
+    let world = resolve.select_world(pkg, None).unwrap();
+    let mut core = std::fs::read(lld.file("core.wasm")).unwrap();
+    wit_component::embed_component_metadata(
+        &mut core,
+        &resolve,
+        world,
+        wit_component::StringEncoding::UTF16,
+    )
+    .unwrap();
+    std::fs::write(lld.file("core.wasm"), &core).unwrap();
+
+    lld.link_ok(&["--string-encoding", "utf16"]);
+    lld.link_ok(&[]);
+    let stderr = lld.link_err(&["--string-encoding", "utf8"]);
+    assert!(
+        stderr.contains("`--string-encoding utf8` conflicts with the `utf16` string encoding"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn list_sections() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let stderr = lld.link_ok(&["--list-sections"]);
+    assert!(stderr.contains("core module section: "), "{stderr}");
+    assert!(stderr.contains("import section: "), "{stderr}");
+    assert!(
+        stderr.contains("custom section \"producers\": "),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn drop_import() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&[]);
+    let stdout = component_imports(&lld.output())
+        .into_iter()
+        .find(|name| name.starts_with("wasi:cli/stdout@"))
+        .unwrap();
+
+    // `unused` is part of the world but the core module never imports it, so
+    // it can be dropped.
+    let wit = "package test:drop; world w { import unused: func(); }";
+    lld.link_ok(&["--component-type-inline", wit, "--drop-import", "unused"]);
+    assert_component(&lld.output());
+    assert!(!component_imports(&lld.output()).contains(&"unused".to_string()));
+
+    let stderr = lld.link_err(&["--drop-import", &stdout]);
+    assert!(
+        stderr.contains(&format!("cannot drop import `{stdout}`")),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn keep_abi_helpers() {
+    let lld = FakeLld::new(
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+                    i32.const 0)
+            )
+        "#,
+    );
+    lld.link_ok(&["--wasi-adapter=none"]);
+    assert!(component_exports(&lld.output()).is_empty());
+
+    lld.link_ok(&["--wasi-adapter=none", "--keep-abi-helpers"]);
+    assert_component(&lld.output());
+    assert_eq!(component_exports(&lld.output()), ["cabi-realloc"]);
+
+    lld.link_ok(&[
+        "--wasi-adapter=none",
+        "--keep-abi-helpers",
+        "--hide-abi-helpers",
+    ]);
+    assert!(component_exports(&lld.output()).is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn format_version() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--format-version=13"]);
+    let output = lld.output();
+    assert_component(&output);
+    assert_eq!(output[4..6], [0x0d, 0x00]);
+
+    let stderr = lld.link_err(&["--format-version=12"]);
+    assert!(
+        stderr.contains("component format version 12 is not supported"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn embed_build_info() {
+    let build_info = |wasm: &[u8]| -> serde_json::Value {
+        let data = wasmparser::Parser::new(0)
+            .parse_all(wasm)
+            .find_map(|payload| match payload.unwrap() {
+                Payload::CustomSection(s) if s.name() == "wasm-component-ld-build-info" => {
+                    Some(s.data().to_vec())
+                }
+                _ => None,
+            })
+            .unwrap();
+        serde_json::from_slice(&data).unwrap()
+    };
+
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--embed-build-info"]);
+    let output = lld.output();
+    assert_component(&output);
+    let info = build_info(&output);
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(info["args"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|a| a == "--embed-build-info"));
+    assert!(info["timestamp"].is_u64());
+
+    lld.link_ok(&["--embed-build-info", "--deterministic"]);
+    let info = build_info(&lld.output());
+    assert!(info.get("timestamp").is_none());
+}
+
+#[test]
+fn print_adapter() {
+    for adapter in ["command", "reactor", "proxy"] {
+        let output = Command::new(component_ld())
+            .arg(format!("--print-adapter={adapter}"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        wasmparser::Validator::new()
+            .validate_all(&output.stdout)
+            .unwrap();
+
+        let output = Command::new(component_ld())
+            .arg(format!("--print-adapter={adapter}"))
+            .arg("--output-wat")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let wat = String::from_utf8(output.stdout).unwrap();
+        wasmparser::Validator::new()
+            .validate_all(&wat::parse_str(&wat).unwrap())
+            .unwrap();
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn rename_import_func() {
+    let lld = FakeLld::new(
+        r#"
+            (module
+                (import "test:rename/a" "f" (func (param i32)))
+                (memory (export "memory") 1)
+            )
+        "#,
+    );
+    let wit = lld.file("rename.wit");
+    std::fs::write(
+        &wit,
+        "
+            package test:rename;
+            interface a { f: func(x: u32); }
+            interface b { g: func(x: u32); h: func(x: string); }
+            world w { import a; import b; }
+        ",
+    )
+    .unwrap();
+    let wit = wit.to_str().unwrap();
+
+    lld.link_ok(&[
+        "--wasi-adapter=none",
+        "--component-type",
+        wit,
+        "--rename-import-func",
+        "test:rename/a#f=test:rename/b#g",
+    ]);
+    let output = lld.output();
+    assert_component(&output);
+    let imports = component_imports(&output);
+    assert_eq!(imports, ["test:rename/b"]);
+
+    let stderr = lld.link_err(&[
+        "--wasi-adapter=none",
+        "--component-type",
+        wit,
+        "--rename-import-func",
+        "test:rename/a#f=test:rename/b#h",
+    ]);
+    assert!(stderr.contains("since their signatures differ"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn verbose_prints_world() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let stderr = lld.link_ok(&[
+        "--verbose",
+        "--component-type-inline",
+        "package test:verbose; world w { import printed-func: func(); }",
+    ]);
+    assert!(stderr.contains("encoding component with world"), "{stderr}");
+    assert!(stderr.contains("import printed-func: func();"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn passthrough_after_double_dash() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let output = Command::new(component_ld())
+        .arg("--wasm-ld-path")
+        .arg(lld.path())
+        .arg("-o")
+        .arg(lld.output_path())
+        .arg("--export=a")
+        .arg("--")
+        .arg("--whole-archive")
+        .arg("--wasi-adapter=command")
+        .arg("lib.a")
+        .arg("--no-whole-archive")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let args = lld.args();
+    let start = args.iter().position(|a| a == "--export=a").unwrap();
+    assert_eq!(
+        args[start..start + 5],
+        [
+            "--export=a",
+            "--whole-archive",
+            "--wasi-adapter=command",
+            "lib.a",
+            "--no-whole-archive",
+        ]
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn no_exports_warning() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let stderr = lld.link_ok(&[]);
+    assert!(
+        stderr.contains("warning: the component has no exports"),
+        "{stderr}"
+    );
+
+    let stderr = lld.link_err(&["--fail-on-warnings"]);
+    assert!(stderr.contains("the component has no exports"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn sort_imports() {
+    let lld = FakeLld::new(
+        r#"
+            (module
+                (import "test:b/y" "g" (func))
+                (import "test:a/x" "f" (func))
+                (memory (export "memory") 1)
+            )
+        "#,
+    );
+    let a = "package test:a; interface x { f: func(); } world w { import x; }";
+    let b = "package test:b; interface y { g: func(); } world w { import y; }";
+    let link = |first: &str, second: &str, sort: bool| {
+        let mut args = vec![
+            "--wasi-adapter=none",
+            "--deterministic",
+            "--component-type-inline",
+            first,
+            "--component-type-inline",
+            second,
+        ];
+        if sort {
+            args.push("--sort-imports");
+        }
+        lld.link_ok(&args);
+        let output = lld.output();
+        assert_component(&output);
+        output
+    };
+    assert_eq!(
+        component_imports(&link(b, a, false)),
+        ["test:b/y", "test:a/x"]
+    );
+
+    let ab = link(a, b, true);
+    let ba = link(b, a, true);
+    assert_eq!(component_imports(&ab), ["test:a/x", "test:b/y"]);
+    assert_eq!(ab, ba);
+}
+
+#[test]
+#[cfg(unix)]
+fn adapter_for() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "my_module" "f" (func))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let good = lld.file("good.wat");
+    std::fs::write(&good, r#"(module (func (export "f")))"#).unwrap();
+    let bad = lld.file("bad.wat");
+    std::fs::write(&bad, r#"(module (func (export "g")))"#).unwrap();
+
+    lld.link_ok(&[&format!("--adapter-for=my_module={}", good.display())]);
+    assert_component(&lld.output());
+
+    let stderr = lld.link_err(&[&format!("--adapter-for=my_module={}", bad.display())]);
+    assert!(stderr.contains("does not export `f`"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn component_type_gzip() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(b"package test:gz;\nworld gz { import gzipped-func: func(); }\n")
+        .unwrap();
+    let gz = encoder.finish().unwrap();
+
+    for name in ["types.wit.gz", "no-extension"] {
+        let wit = lld.file(name);
+        std::fs::write(&wit, &gz).unwrap();
+        let stderr = lld.link_ok(&["--verbose", "--component-type", wit.to_str().unwrap()]);
+        assert_component(&lld.output());
+        assert!(stderr.contains("import gzipped-func: func();"), "{stderr}");
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn component_type_not_wit() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let core = lld.file("core.wasm");
+    let stderr = lld.link_err(&["--component-type", core.to_str().unwrap()]);
+    assert!(
+        stderr.contains("expected a WIT file but") && stderr.contains("is a WebAssembly binary"),
+        "{stderr}"
+    );
+
+    let object = lld.file("foo.o");
+    std::fs::write(&object, [0x7f, b'E', b'L', b'F', 0xff, 0xfe]).unwrap();
+    let stderr = lld.link_err(&["--component-type", object.to_str().unwrap()]);
+    assert!(stderr.contains("contains binary data"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn output_template() {
+    let lld = FakeLld::new(r#"(module (memory (export "memory") 1))"#);
+    for (adapter, name) in [("none", "out-none.wasm"), ("reactor", "out-reactor.wasm")] {
+        let template = lld.file("out-{adapter}.wasm");
+        let output = Command::new(component_ld())
+            .arg("--wasm-ld-path")
+            .arg(lld.path())
+            .arg(format!("--wasi-adapter={adapter}"))
+            .arg("-o")
+            .arg(&template)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        assert!(lld.file(name).exists());
+        assert!(!template.exists());
+    }
+
+    let template = lld.file("{kind}.wasm");
+    let output = Command::new(component_ld())
+        .arg("--wasm-ld-path")
+        .arg(lld.path())
+        .arg("-o")
+        .arg(&template)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(lld.file("reactor.wasm").exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn print_world_diff() {
+    let lld = FakeLld::new(
+        r#"
+            (module
+                (func (export "foo"))
+                (memory (export "memory") 1)
+            )
+        "#,
+    );
+    let baseline = lld.file("baseline.wit");
+    std::fs::write(&baseline, "package test:diff; world w {}").unwrap();
+    let baseline = format!("--baseline-wit={}", baseline.display());
+
+    let stderr = lld.link_ok(&[
+        "--wasi-adapter=none",
+        "--component-type-inline",
+        "package test:diff; world w { export foo: func(); }",
+        &baseline,
+        "--print-world-diff",
+    ]);
+    assert_component(&lld.output());
+    assert!(stderr.contains("  + export foo\n"), "{stderr}");
+
+    let stderr = lld.link_ok(&["--wasi-adapter=none", &baseline, "--print-world-diff"]);
+    assert!(stderr.contains("(no changes)"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn long_output_name() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let name = format!("{}.wasm", "a".repeat(240));
+    let output = Command::new(component_ld())
+        .arg("--wasm-ld-path")
+        .arg(lld.path())
+        .arg("-o")
+        .arg(lld.file(&name))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_component(&std::fs::read(lld.file(&name)).unwrap());
+
+    // The temporary core module's name is truncated.
+    let args = lld.args();
+    let temp = &args[args.iter().position(|a| a == "-o").unwrap() + 1];
+    assert!(temp.ends_with(&format!("/{}", "a".repeat(128))), "{temp}");
+}
+
+#[test]
+#[cfg(unix)]
+fn package_metadata() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&[
+        "--package-name=my-org:my-component",
+        "--package-version=1.2.3",
+        "--authors=Alice,Bob",
+    ]);
+    let output = lld.output();
+    assert_component(&output);
+    match wasm_metadata::Metadata::from_binary(&output).unwrap() {
+        wasm_metadata::Metadata::Component {
+            name,
+            registry_metadata,
+            ..
+        } => {
+            assert_eq!(name.as_deref(), Some("my-org:my-component@1.2.3"));
+            let authors = registry_metadata.unwrap().get_authors().cloned();
+            assert_eq!(authors, Some(vec!["Alice".to_string(), "Bob".to_string()]));
+        }
+        wasm_metadata::Metadata::Module { .. } => panic!("expected a component"),
+    }
+
+    let stderr = lld.link_err(&["--package-name=NotValid"]);
+    assert!(stderr.contains("invalid package name"), "{stderr}");
+    let stderr = lld.link_err(&["--package-name=a:b", "--package-version=1.x"]);
+    assert!(stderr.contains("--package-version"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn preview1_aliases() {
+    let lld = FakeLld::new(
+        r#"
+            (module
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_unstable" "fd_write"
+                    (func (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+            )
+        "#,
+    );
+    let stderr = lld.link_err(&[]);
+    assert!(
+        stderr.contains("the core module imports from `wasi_unstable`"),
+        "{stderr}"
+    );
+
+    lld.link_ok(&["--preview1-aliases=wasi_unstable"]);
+    assert_component(&lld.output());
+}
+
+#[test]
+#[cfg(unix)]
+fn sbom_out() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let sbom = lld.file("sbom.json");
+    lld.link_ok(&["--sbom-out", sbom.to_str().unwrap()]);
+    let sbom: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&sbom).unwrap()).unwrap();
+    assert_eq!(sbom["bomFormat"], "CycloneDX");
+    assert_eq!(sbom["metadata"]["tools"][0]["name"], "wasm-component-ld");
+    let components = sbom["components"].as_array().unwrap();
+    assert!(components.iter().any(|c| {
+        c["name"] == "wasi_snapshot_preview1"
+            && c["description"].as_str().unwrap().contains("`reactor`")
+    }));
+    assert!(components
+        .iter()
+        .any(|c| c["name"] == "wasi:io" && c["description"] == "WIT package"));
+}
+
+#[test]
+#[cfg(unix)]
+fn validate_only() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&[]);
+
+    let output = Command::new(component_ld())
+        .arg("--validate-only")
+        .arg(lld.output_path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let mut bad = lld.output();
+    bad.truncate(bad.len() / 2);
+    let bad_path = lld.file("bad.wasm");
+    std::fs::write(&bad_path, &bad).unwrap();
+    let output = Command::new(component_ld())
+        .arg("--validate-only")
+        .arg(&bad_path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("is not valid"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn adapt_encoding() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "a" "f" (func))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+
+    let mut resolve = wit_parser::Resolve::default();
+    let wit = "package test:adapter; world w { import log: func(s: string); }";
+    let group = wit_parser::UnresolvedPackageGroup::parse("adapter.wit".as_ref(), wit).unwrap();
+    let pkg = resolve.append(group).unwrap()[0];
+    let world = resolve.select_world(pkg, None).unwrap();
+    let mut adapter = wat::parse_str(r#"(module (func (export "f")))"#).unwrap();
+    wit_component::embed_component_metadata(
+        &mut adapter,
+        &resolve,
+        world,
+        wit_component::StringEncoding::UTF16,
+    )
+    .unwrap();
+    let path = lld.file("a.wasm");
+    std::fs::write(&path, &adapter).unwrap();
+    let adapt = format!("--adapt={}", path.display());
+
+    lld.link_ok(&[&adapt, "--adapt-encoding=a=utf16"]);
+    assert_component(&lld.output());
+
+    let stderr = lld.link_err(&[&adapt, "--adapt-encoding=a=utf8"]);
+    assert!(
+        stderr.contains("`--adapt-encoding a=utf8` conflicts with the `utf16`"),
+        "{stderr}"
+    );
+}
+
+/// Makes every function body of the top-level core modules of `component`
+/// for which `pick` returns true invalid, by leaving an extra value on the
+/// stack. `pick` is given the module's name.
+fn break_module_code(component: &[u8], pick: impl Fn(Option<&str>) -> bool) -> Vec<u8> {
+    let break_code = |wasm: &[u8]| {
+        let mut module = wasm_encoder::Module::new();
+        let mut code = None;
+        for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+            match payload.unwrap() {
+                Payload::CodeSectionStart { .. } => code = Some(wasm_encoder::CodeSection::new()),
+                Payload::CodeSectionEntry(body) => {
+                    let mut bytes = wasm[body.range()].to_vec();
+                    bytes.pop();
+                    bytes.extend([0x41, 0x00, 0x0b]); // i32.const 0, end
+                    code.as_mut().unwrap().raw(&bytes);
+                }
+                payload => {
+                    if let Some(code) = code.take() {
+                        module.section(&code);
+                    }
+                    if let Some((id, range)) = payload.as_section() {
+                        module.section(&wasm_encoder::RawSection {
+                            id,
+                            data: &wasm[range],
+                        });
+                    }
+                }
+            }
+        }
+        module
+    };
+
+    let mut ret = wasm_encoder::Component::new();
+    let mut depth = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(component) {
+        let payload = payload.unwrap();
+        let section = payload.as_section().filter(|_| depth == 0);
+        match &payload {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            _ => {}
+        }
+        let Some((id, range)) = section else {
+            continue;
+        };
+        if let Payload::ModuleSection {
+            unchecked_range, ..
+        } = &payload
+        {
+            let wasm = &component[unchecked_range.clone()];
+            let name = match wasm_metadata::Metadata::from_binary(wasm).unwrap() {
+                wasm_metadata::Metadata::Module { name, .. } => name,
+                wasm_metadata::Metadata::Component { .. } => None,
+            };
+            if pick(name.as_deref()) {
+                ret.section(&wasm_encoder::ModuleSection(&break_code(wasm)));
+                continue;
+            }
+        }
+        ret.section(&wasm_encoder::RawSection {
+            id,
+            data: &component[range],
+        });
+    }
+    ret.finish()
+}
+
+#[test]
+#[cfg(unix)]
+fn skip_adapter_validation() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "a" "f" (func))
+    (memory (export "memory") 1)
+    (func (export "g") call 0)
+)
+"#,
+    );
+    let adapter = lld.file("a.wasm");
+    std::fs::write(
+        &adapter,
+        wat::parse_str(r#"(module (func (export "f")))"#).unwrap(),
+    )
+    .unwrap();
+
+    // Links with a post-encode hook which breaks the code of the modules
+    // picked by `pick`, standing in for a buggy adapter.
+    let link = |pick: fn(Option<&str>) -> bool, skip: bool| {
+        let mut args = vec![
+            "--wasm-ld-path".into(),
+            lld.path().into_os_string(),
+            "--adapt".into(),
+            adapter.clone().into_os_string(),
+            "-o".into(),
+            lld.output_path().into_os_string(),
+        ];
+        if skip {
+            args.push("--skip-adapter-validation".into());
+        }
+        wasm_component_ld::Linker::new(args)
+            .with_post_encode(Box::new(move |component| {
+                *component = break_module_code(component, pick);
+                Ok(())
+            }))
+            .run()
+    };
+    let is_adapter = |name: Option<&str>| name == Some("wit-component:adapter:a");
+    let is_main = |name: Option<&str>| name.is_none();
+
+    let err = link(is_adapter, false).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("failed to validate component output"),
+        "{err:?}"
+    );
+    link(is_adapter, true).unwrap();
+    assert!(wasmparser::Parser::is_component(&lld.output()));
+
+    // Invalid code outside of the adapter is still rejected.
+    let err = link(is_main, true).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("failed to validate component output"),
+        "{err:?}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn component_type_multiple_worlds() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let wit = "package test:worlds; world first {} world second { import f: func(); }";
+    let stderr = lld.link_err(&["--component-type-inline", wit]);
+    assert!(
+        stderr.contains("contains multiple worlds (`first`, `second`)"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("--world=NAME"), "{stderr}");
+
+    let stderr = lld.link_ok(&[
+        "--verbose",
+        "--component-type-inline",
+        wit,
+        "--world=second",
+    ]);
+    assert!(stderr.contains("import f: func();"), "{stderr}");
+
+    // `--world` is checked against packages with a single world too.
+    let single = "package test:single; world only { import f: func(); }";
+    let stderr = lld.link_err(&["--component-type-inline", single, "--world=other"]);
+    assert!(stderr.contains("unable to select a world"), "{stderr}");
+    lld.link_ok(&["--component-type-inline", single, "--world=only"]);
+}
+
+#[test]
+#[cfg(unix)]
+fn config_file() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let config = lld.file("config.toml");
+    std::fs::write(
+        &config,
+        r#"
+wasi-adapter = "none"
+append-lld-flags = ["--gc-sections"]
+"#,
+    )
+    .unwrap();
+    let config = format!("--config={}", config.display());
+
+    // Without an adapter the WASI imports of the module can't be satisfied.
+    lld.link_err(&[&config]);
+    assert!(lld.args().iter().any(|a| a == "--gc-sections"));
+
+    // Flags on the command line take precedence over the config file.
+    lld.link_ok(&[&config, "--wasi-adapter=reactor"]);
+    assert_component(&lld.output());
+
+    std::fs::write(lld.file("bad.toml"), "adapter = \"none\"\n").unwrap();
+    let stderr = lld.link_err(&[&format!("--config={}", lld.file("bad.toml").display())]);
+    assert!(stderr.contains("unknown key `adapter`"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn component_type_override() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let base = "package test:base; world w { import f: func(); import g: func(); }";
+    let over = lld.file("override.wit");
+    std::fs::write(
+        &over,
+        "package test:over; world w { import f: func(x: u32); }",
+    )
+    .unwrap();
+
+    let stderr = lld.link_err(&[
+        "--component-type-inline",
+        base,
+        &format!("--component-type={}", over.display()),
+    ]);
+    assert!(stderr.contains("failed to merge"), "{stderr}");
+
+    let stderr = lld.link_ok(&[
+        "--verbose",
+        "--component-type-inline",
+        base,
+        &format!("--component-type-override={}", over.display()),
+    ]);
+    assert!(stderr.contains("import f: func(x: u32);"), "{stderr}");
+    assert!(stderr.contains("import g: func();"), "{stderr}");
+    assert_component(&lld.output());
+}
+
+#[test]
+#[cfg(unix)]
+fn component_export_all() {
+    let lld = FakeLld::new(
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "add_one") (param i32) (result i32)
+                    local.get 0
+                    i32.const 1
+                    i32.add)
+                (func (export "two_results") (result i32 i32)
+                    i32.const 0
+                    i32.const 0)
+                (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+                    i32.const 0)
+            )
+        "#,
+    );
+    lld.link_ok(&["--wasi-adapter=none"]);
+    assert!(component_exports(&lld.output()).is_empty());
+
+    let stderr = lld.link_ok(&["--wasi-adapter=none", "--component-export-all"]);
+    assert_component(&lld.output());
+    let mut exports = component_exports(&lld.output());
+    exports.sort();
+    assert_eq!(exports, ["add-one", "cabi-realloc"]);
+    assert!(
+        stderr.contains("exports ABI helpers: `cabi-realloc`"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn objects_order() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let order = lld.file("order.txt");
+    std::fs::write(&order, "# link c first\nc.o\n\na.o\n").unwrap();
+    let order = format!("--objects-order={}", order.display());
+    let objects = || {
+        lld.args()
+            .into_iter()
+            .filter(|a| a.ends_with(".o"))
+            .collect::<Vec<_>>()
+    };
+
+    lld.link_ok(&["a.o", "b.o", "--export=f", "c.o"]);
+    assert_eq!(objects(), ["a.o", "b.o", "c.o"]);
+
+    lld.link_ok(&["a.o", "b.o", "--export=f", "c.o", &order]);
+    assert_eq!(objects(), ["c.o", "a.o", "b.o"]);
+    assert!(lld.args().iter().any(|a| a == "--export=f"));
+
+    let stderr = lld.link_err(&["a.o", "b.o", &order]);
+    assert!(
+        stderr.contains("object `c.o` listed in") && stderr.contains("was not passed"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn adapter_imports_on_failure() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "my_adapter" "f" (func))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let adapter = lld.file("adapter.wat");
+    std::fs::write(
+        &adapter,
+        r#"
+(module
+    (import "my:host/thing" "do-it" (func $do_it))
+    (import "my:host/thing" "do-other" (func))
+    (func (export "f") call $do_it)
+)
+"#,
+    )
+    .unwrap();
+
+    let stderr = lld.link_err(&[
+        "--wasi-adapter=none",
+        &format!("--adapt=my_adapter={}", adapter.display()),
+    ]);
+    assert!(stderr.contains("adapter `my_adapter` imports:"), "{stderr}");
+    assert!(
+        stderr.contains("my:host/thing: do-it, do-other"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn opt_level() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--opt-level=2"]);
+    let args = lld.args();
+    let i = args.iter().position(|a| a == "-O").unwrap();
+    assert_eq!(args[i + 1], "2");
+    assert!(args.iter().any(|a| a == "--lto-O=2"));
+
+    let stderr = lld.link_err(&["--opt-level=4"]);
+    assert!(stderr.contains("--opt-level"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn max_imports_and_exports() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "$root" "a" (func))
+    (import "$root" "b" (func))
+    (memory (export "memory") 1)
+    (func (export "x"))
+    (func (export "y"))
+)
+"#,
+    );
+    let wit = "package test:max; world w { import a: func(); import b: func(); \
+               export x: func(); export y: func(); }";
+    let inline = ["--wasi-adapter=none", "--component-type-inline", wit];
+
+    lld.link_ok(&[&inline[..], &["--max-imports=2", "--max-exports=2"]].concat());
+
+    let stderr = lld.link_err(&[&inline[..], &["--max-imports=1"]].concat());
+    assert!(
+        stderr.contains("the component has 2 imports, more than the maximum of 1"),
+        "{stderr}"
+    );
+    let stderr = lld.link_err(&[&inline[..], &["--max-exports=1"]].concat());
+    assert!(
+        stderr.contains("the component has 2 exports, more than the maximum of 1"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn intermediate_memory() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--intermediate=memory"]);
+    assert_component(&lld.output());
+
+    lld.link_ok(&["--intermediate=file"]);
+    assert_component(&lld.output());
+
+    let stderr = lld.link_err(&["--intermediate=disk"]);
+    assert!(stderr.contains("must be one of: memory, file"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn github_annotations() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let wit = "package test:empty; world empty {}";
+    let stderr = lld.link_ok(&["--component-type-inline", wit, "--github-annotations"]);
+    assert!(
+        stderr.contains("::warning::component type world `empty` has no imports or exports"),
+        "{stderr}"
+    );
+
+    let stderr = lld.link_err(&["--github-annotations", "--config=nonexistent.toml"]);
+    assert!(
+        stderr.starts_with("::error::failed to read config file"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn artifacts_prefix() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let prefix = lld.file("build/foo");
+    lld.link_ok(&[&format!("--artifacts-prefix={}", prefix.display())]);
+
+    let core = std::fs::read(lld.file("build/foo.core.wasm")).unwrap();
+    assert!(!wasmparser::Parser::is_component(&core));
+    assert_eq!(
+        std::fs::read(lld.file("build/foo.wasm")).unwrap(),
+        lld.output()
+    );
+    let wit = std::fs::read_to_string(lld.file("build/foo.wit")).unwrap();
+    assert!(wit.contains("world"), "{wit}");
+    let manifest = std::fs::read_to_string(lld.file("build/foo.json")).unwrap();
+    assert!(manifest.contains("foo.core.wasm"), "{manifest}");
+    assert!(manifest.contains("\"imports\""), "{manifest}");
+}
+
+#[test]
+#[cfg(unix)]
+fn reject_imports_without_version() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "test:pkg/i" "f" (func))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let wit = "package test:pkg; interface i { f: func(); } world w { import i; }";
+    let stderr = lld.link_err(&[
+        "--wasi-adapter=none",
+        "--component-type-inline",
+        wit,
+        "--reject-imports-without-version",
+    ]);
+    assert!(
+        stderr.contains("the component has imports without a version: `test:pkg/i`"),
+        "{stderr}"
+    );
+
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "test:pkg/i@1.0.0" "f" (func))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let wit = "package test:pkg@1.0.0; interface i { f: func(); } world w { import i; }";
+    lld.link_ok(&[
+        "--wasi-adapter=none",
+        "--component-type-inline",
+        wit,
+        "--reject-imports-without-version",
+    ]);
+}
+
+#[test]
+#[cfg(unix)]
+fn pre_link_check() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let object = |name: &str, features: &[(u8, &str)]| {
+        let mut data = vec![features.len() as u8];
+        for (prefix, feature) in features {
+            data.push(*prefix);
+            data.push(feature.len() as u8);
+            data.extend_from_slice(feature.as_bytes());
+        }
+        let mut module = wasm_encoder::Module::new();
+        module.section(&wasm_encoder::CustomSection {
+            name: "target_features".into(),
+            data: data.into(),
+        });
+        let path = lld.file(name);
+        std::fs::write(&path, module.finish()).unwrap();
+        path.display().to_string()
+    };
+    let a = object("a.o", &[(b'+', "mutable-globals"), (b'+', "sign-ext")]);
+    let b = object("b.o", &[(b'+', "sign-ext")]);
+    let c = object("c.o", &[(b'-', "mutable-globals")]);
+
+    lld.link_ok(&["--pre-link-check", &a, &b]);
+
+    let stderr = lld.link_err(&["--pre-link-check", &a, &c]);
+    assert!(
+        stderr.contains("disallows feature `mutable-globals` which"),
+        "{stderr}"
+    );
+
+    // Without the flag the objects are passed to `wasm-ld` unchecked.
+    lld.link_ok(&[&a, &c]);
+}
+
+#[test]
+#[cfg(unix)]
+fn is_command_and_is_reactor() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (memory (export "memory") 1)
+    (func (export "_start"))
+)
+"#,
+    );
+    let link = |flags: &[&str]| {
+        Command::new(component_ld())
+            .arg("--wasm-ld-path")
+            .arg(lld.path())
+            .args(flags)
+            .arg("-o")
+            .arg(lld.file("{kind}.wasm"))
+            .output()
+            .unwrap()
+    };
+
+    for (flag, kind) in [("--is-command", "command"), ("--is-reactor", "reactor")] {
+        let output = link(&[flag]);
+        assert!(output.status.success(), "{output:?}");
+        let path = lld.file(&format!("{kind}.wasm"));
+        assert!(path.exists());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    let output = link(&["--is-command", "--is-reactor"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("cannot be used with"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn print_core_imports() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let stderr = lld.link_ok(&["--print-core-imports"]);
+    assert!(stderr.contains("core module imports:"), "{stderr}");
+    assert!(
+        stderr.contains("  wasi_snapshot_preview1.fd_write"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn emit_host_stub() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "$root" "host-log" (func))
+    (memory (export "memory") 1)
+    (func (export "run"))
+)
+"#,
+    );
+    let wit = "package test:stub; world w { import host-log: func(); export run: func(); }";
+    let stub = lld.file("host.wit");
+    lld.link_ok(&[
+        "--wasi-adapter=none",
+        "--component-type-inline",
+        wit,
+        &format!("--emit-host-stub={}", stub.display()),
+    ]);
+    let stub = std::fs::read_to_string(stub).unwrap();
+    assert!(stub.contains("import host-log: func();"), "{stub}");
+    assert!(!stub.contains("export run"), "{stub}");
+}
+
+#[test]
+#[cfg(unix)]
+fn emit_core_wit() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "test:core/api" "f" (func))
+    (memory (export "memory") 1)
+    (func (export "run"))
+)
+"#,
+    );
+    let wit = "
+        package test:core;
+        interface api { f: func(); }
+        world w { import api; export run: func(); }
+    ";
+    let core_wit = lld.file("core.wit");
+    lld.link_ok(&[
+        "--wasi-adapter=none",
+        "--component-type-inline",
+        wit,
+        &format!("--emit-core-wit={}", core_wit.display()),
+    ]);
+    let core_wit = std::fs::read_to_string(core_wit).unwrap();
+
+    let mut resolve = wit_parser::Resolve::default();
+    let group = wit_parser::UnresolvedPackageGroup::parse("input.wit".as_ref(), wit).unwrap();
+    let packages = resolve.append(group).unwrap();
+    let expected = wit_component::WitPrinter::default()
+        .print(&resolve, &packages)
+        .unwrap();
+    assert_eq!(core_wit, expected);
+}
+
+#[test]
+#[cfg(unix)]
+fn on_unresolved() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "env" "host_value" (func (param i32) (result i32)))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+
+    let stderr = lld.link_err(&["--allow-undefined", "--on-unresolved=error"]);
+    assert!(
+        stderr.contains("the core module has unresolved symbols: `host_value`"),
+        "{stderr}"
+    );
+
+    lld.link_ok(&["--allow-undefined", "--on-unresolved=import"]);
+    assert_component(&lld.output());
+    let imports = component_imports(&lld.output());
+    assert_eq!(imports, ["host-value"]);
+}
+
+#[test]
+#[cfg(unix)]
+fn strict_temp() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let output = Command::new(component_ld())
+        .arg("--wasm-ld-path")
+        .arg(lld.path())
+        .arg("--strict-temp")
+        .arg("-o")
+        .arg(lld.file(".."))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("has no usable file name"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn print_wit_packages() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let wit = lld.file("wit");
+    std::fs::create_dir_all(wit.join("deps/dep")).unwrap();
+    std::fs::write(
+        wit.join("world.wit"),
+        "package test:app; world w { import test:dep/i@1.0.0; }",
+    )
+    .unwrap();
+    std::fs::write(
+        wit.join("deps/dep/i.wit"),
+        "package test:dep@1.0.0; interface i { f: func(); }",
+    )
+    .unwrap();
+
+    let stderr = lld.link_ok(&[
+        "--print-wit-packages",
+        &format!("--component-type={}", wit.display()),
+    ]);
+    assert!(stderr.contains("WIT packages:"), "{stderr}");
+    assert!(stderr.contains("  test:app\n"), "{stderr}");
+    assert!(stderr.contains("  test:dep@1.0.0\n"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn adapter_name() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let module_names = |wasm: &[u8]| match wasm_metadata::Metadata::from_binary(wasm).unwrap() {
+        wasm_metadata::Metadata::Component { children, .. } => children
+            .into_iter()
+            .filter_map(|child| match *child {
+                wasm_metadata::Metadata::Module { name, .. } => name,
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        _ => unreachable!(),
+    };
+
+    lld.link_ok(&[]);
+    assert!(module_names(&lld.output())
+        .contains(&"wit-component:adapter:wasi_snapshot_preview1".to_string()));
+
+    lld.link_ok(&["--adapter-name=wasi_snapshot_preview1=my-wasi"]);
+    assert_component(&lld.output());
+    let names = module_names(&lld.output());
+    assert!(names.contains(&"my-wasi".to_string()), "{names:?}");
+
+    let stderr = lld.link_err(&["--adapter-name=missing=label"]);
+    assert!(stderr.contains("no such adapter was injected"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn check_determinism() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--check-determinism"]);
+    assert_component(&lld.output());
+
+    lld.link_ok(&[
+        "--check-determinism",
+        "--deterministic",
+        "--embed-build-info",
+    ]);
+    assert_component(&lld.output());
+}
+
+#[test]
+#[cfg(unix)]
+fn save_temps_reported() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    // A linker which additionally writes an LTO intermediate next to its
+    // output, like `wasm-ld --save-temps`.
+    let script = write_script(
+        lld.dir.path(),
+        "save-temps-ld",
+        &format!(
+            "{:?} \"$@\"\nwhile [ $# -gt 0 ]; do\n    \
+             if [ \"$1\" = \"-o\" ]; then echo lto > \"$2.lto.o\"; fi\n    \
+             shift\ndone\n",
+            lld.path()
+        ),
+    );
+
+    let output = Command::new(component_ld())
+        .arg("--wasm-ld-path")
+        .arg(&script)
+        .arg("--save-temps")
+        .arg("--verbose")
+        .arg("-o")
+        .arg(lld.output_path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("saved `wasm-ld` temporary file to"),
+        "{stderr}"
+    );
+    assert!(lld.file("out.wasm.lto.o").exists());
+}
+
+#[test]
+#[cfg(unix)]
+fn also_emit_core() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let core = lld.file("core.p1.wasm");
+    lld.link_ok(&[&format!("--also-emit-core={}", core.display())]);
+
+    let component = lld.output();
+    assert_component(&component);
+    let core = std::fs::read(core).unwrap();
+    assert!(!wasmparser::Parser::is_component(&core));
+    wasmparser::Validator::new().validate_all(&core).unwrap();
+    assert_ne!(core, component);
+}
+
+#[test]
+#[cfg(unix)]
+fn string_encoding_names() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--string-encoding=COMPACT-UTF16"]);
+    lld.link_ok(&["--string-encoding=compact_utf16"]);
+
+    let stderr = lld.link_err(&["--string-encoding=latin1"]);
+    assert!(
+        stderr.contains("must be one of: utf8, utf16, compact-utf16"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn proxy_imports_strict() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--wasi-adapter=proxy", "--proxy-imports=strict"]);
+    let imports = component_imports(&lld.output());
+    assert!(!imports.is_empty());
+    assert!(
+        imports.iter().all(|i| i.starts_with("wasi:")),
+        "{imports:?}"
+    );
+
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32)))
+    (import "$root" "extra" (func))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let args = [
+        "--wasi-adapter=proxy",
+        "--component-type-inline",
+        "package test:proxy; world w { import extra: func(); }",
+    ];
+    lld.link_ok(&args);
+    let stderr = lld.link_err(&[&args[..], &["--proxy-imports=strict"]].concat());
+    assert!(
+        stderr.contains("aren't part of the `wasi:http/proxy` world: `extra`"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn component_type_binary_package() {
+    let lld = FakeLld::new(REACTOR_WAT);
+
+    let mut resolve = wit_parser::Resolve::default();
+    let wit = "package test:binary; world w { import binary-func: func(); }";
+    let group = wit_parser::UnresolvedPackageGroup::parse("binary.wit".as_ref(), wit).unwrap();
+    let pkg = resolve.append(group).unwrap()[0];
+    let wasm = wit_component::encode(Some(true), &resolve, pkg).unwrap();
+    let path = lld.file("binary.wasm");
+    std::fs::write(&path, wasm).unwrap();
+
+    let stderr = lld.link_ok(&["--verbose", "--component-type", path.to_str().unwrap()]);
+    assert!(stderr.contains("import binary-func: func();"), "{stderr}");
+    assert_component(&lld.output());
+}
+
+#[test]
+#[cfg(unix)]
+fn no_temp_cleanup_on_error() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "unknown" "f" (func))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let stderr = lld.link_err(&["--wasi-adapter=none", "--no-temp-cleanup-on-error"]);
+    let start = stderr.find("keeping temporary directory \"").unwrap() + 29;
+    let len = stderr[start..].find('"').unwrap();
+    let dir = PathBuf::from(&stderr[start..][..len]);
+    assert!(dir.is_dir(), "{stderr}");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stderr = lld.link_err(&["--wasi-adapter=none"]);
+    assert!(!stderr.contains("keeping temporary directory"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn verify_exports() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let stderr = lld.link_ok(&[
+        "--verify-exports",
+        "--export=memory",
+        "--export=no_such_thing",
+    ]);
+    assert!(!stderr.contains("`--export=memory`"), "{stderr}");
+    assert!(
+        stderr.contains("`--export=no_such_thing` was passed to `wasm-ld`"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn description() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--description=Does a thing"]);
+    let output = lld.output();
+    assert_component(&output);
+    match wasm_metadata::Metadata::from_binary(&output).unwrap() {
+        wasm_metadata::Metadata::Component {
+            registry_metadata, ..
+        } => {
+            let registry = registry_metadata.unwrap();
+            assert_eq!(
+                registry.get_description().map(|s| s.as_str()),
+                Some("Does a thing")
+            );
+            assert_eq!(registry.get_authors(), None);
+        }
+        wasm_metadata::Metadata::Module { .. } => panic!("expected a component"),
+    }
+}
+
+#[test]
+fn check_component_types() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let a = dir.path().join("a.wit");
+    let b = dir.path().join("b.wit");
+    let c = dir.path().join("c.wit");
+    std::fs::write(&a, "package test:a;\nworld a { import foo: func(); }\n").unwrap();
+    std::fs::write(&b, "package test:b;\nworld b { import bar: func(); }\n").unwrap();
+    std::fs::write(
+        &c,
+        "package test:a;\nworld a { import foo: func(x: u32); }\n",
+    )
+    .unwrap();
+
+    let check = |a: &Path, b: &Path| {
+        Command::new(component_ld())
+            .arg("--check-component-types")
+            .arg("--component-type")
+            .arg(a)
+            .arg("--component-type")
+            .arg(b)
+            .output()
+            .unwrap()
+    };
+    let output = check(&a, &b);
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("merged successfully"), "{stderr}");
+
+    let output = check(&a, &c);
+    assert!(!output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("failed to merge component type"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn strip_producers_before_embed() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (@producers (processed-by "clang" "1.0"))
+    (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let has_producers = |sections: &[(u8, String)]| sections.iter().any(|(_, n)| n == "producers");
+
+    lld.link_ok(&[]);
+    assert!(has_producers(&core_module_sections(&lld.output())[0]));
+
+    lld.link_ok(&["--strip-producers-before-embed"]);
+    let component = lld.output();
+    assert_component(&component);
+    assert!(!has_producers(&core_module_sections(&component)[0]));
+    let mut depth = 0;
+    let mut top_level = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(&component) {
+        match payload.unwrap() {
+            Payload::ModuleSection { .. } | Payload::ComponentSection { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            Payload::CustomSection(s) if depth == 0 && s.name() == "producers" => top_level += 1,
+            _ => {}
+        }
+    }
+    assert_eq!(top_level, 1);
+}
+
+#[test]
+#[cfg(unix)]
+fn lld_name() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let bin = tempfile::TempDir::new().unwrap();
+    write_script(
+        bin.path(),
+        "wasm-ld-18",
+        &format!("exec {:?} \"$@\"\n", lld.path()),
+    );
+
+    let output = lld
+        .command_via_path()
+        .env("PATH", bin.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{output:?}");
+
+    let output = lld
+        .command_via_path()
+        .env("PATH", bin.path())
+        .arg("--lld-name=wasm-ld-18")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_component(&lld.output());
+}
+
+#[test]
+#[cfg(unix)]
+fn embed_wit() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let prefix = lld.file("foo");
+    lld.link_ok(&[
+        "--embed-wit",
+        &format!("--artifacts-prefix={}", prefix.display()),
+    ]);
+    let component = lld.output();
+    assert_component(&component);
+    let embedded = wasmparser::Parser::new(0)
+        .parse_all(&component)
+        .find_map(|payload| match payload.unwrap() {
+            Payload::CustomSection(s) if s.name() == "wasm-component-ld-wit" => {
+                Some(String::from_utf8(s.data().to_vec()).unwrap())
+            }
+            _ => None,
+        })
+        .unwrap();
+    let wit = std::fs::read_to_string(lld.file("foo.wit")).unwrap();
+    assert_eq!(embedded, wit);
+}
+
+#[test]
+#[cfg(unix)]
+fn start_and_initialize() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (func (export "_start"))
+    (func (export "_initialize"))
+)
+"#,
+    );
+    let stderr = lld.link_ok(&["--verbose"]);
+    assert!(
+        stderr.contains("exports both `_start` and `_initialize`"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("detected the `command` WASI adapter"),
+        "{stderr}"
+    );
+    assert_component(&lld.output());
+
+    let stderr = lld.link_ok(&["--wasi-adapter=reactor"]);
+    assert!(!stderr.contains("_initialize"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn trace_wit_merge() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let a = lld.file("a.wit");
+    let b = lld.file("b.wit");
+    std::fs::write(&a, "package test:a;\nworld a { import foo: func(); }\n").unwrap();
+    std::fs::write(&b, "package test:b;\nworld b { import bar: func(); }\n").unwrap();
+    let stderr = lld.link_ok(&[
+        "--trace-wit-merge",
+        &format!("--component-type={}", a.display()),
+        &format!("--component-type={}", b.display()),
+    ]);
+    assert!(
+        stderr.contains(&format!(
+            "component type {a:?} contributed packages: test:a"
+        )),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains(&format!(
+            "component type {b:?} contributed packages: test:b"
+        )),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains(&format!("merged the world of component type {b:?}")),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn output_written_atomically() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let lld = FakeLld::new(REACTOR_WAT);
+    let out = tempfile::TempDir::new().unwrap();
+    let output = out.path().join("out.wasm");
+    std::fs::write(&output, b"old").unwrap();
+    let status = Command::new(component_ld())
+        .arg("--wasm-ld-path")
+        .arg(lld.path())
+        .arg("-o")
+        .arg(&output)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_component(&std::fs::read(&output).unwrap());
+
+    // No temporary files are left behind and the output has the usual
+    // permissions rather than those of a temporary file.
+    let entries = std::fs::read_dir(out.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert_eq!(entries, ["out.wasm"]);
+    let reference = out.path().join("reference");
+    std::fs::write(&reference, b"").unwrap();
+    assert_eq!(
+        std::fs::metadata(&output).unwrap().permissions().mode(),
+        std::fs::metadata(&reference).unwrap().permissions().mode(),
+    );
+
+    // Failing to rename for reasons other than crossing filesystems, here
+    // because the output is a directory, is reported without falling back
+    // to copying.
+    let dir = out.path().join("dir");
+    std::fs::create_dir(&dir).unwrap();
+    let output = Command::new(component_ld())
+        .arg("--wasm-ld-path")
+        .arg(lld.path())
+        .arg("-o")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("failed to write output file"), "{stderr}");
+    assert!(!stderr.contains("copy"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn lld_env() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let env = lld.file("env");
+    let wrapper = write_script(
+        lld.dir.path(),
+        "wrapper",
+        &format!(
+            "echo \"$FOO,$BAR\" > {env:?}\nexec {:?} \"$@\"\n",
+            lld.path()
+        ),
+    );
+
+    let link = |args: &[&str]| {
+        let output = Command::new(component_ld())
+            .env("BAR", "inherited")
+            .arg("--wasm-ld-path")
+            .arg(&wrapper)
+            .args(args)
+            .arg("-o")
+            .arg(lld.output_path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        std::fs::read_to_string(&env).unwrap()
+    };
+    assert_eq!(link(&["--lld-env", "FOO=set"]), "set,inherited\n");
+    assert_eq!(link(&["--lld-env=FOO=set", "--lld-clear-env"]), "set,\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn emit_relocs() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (@custom "reloc.CODE" "\00\00")
+)
+"#,
+    );
+    let stderr = lld.link_ok(&["--emit-relocs"]);
+    let component = lld.output();
+    assert_component(&component);
+    let kept = core_module_sections(&component)
+        .iter()
+        .flatten()
+        .any(|(_, name)| name == "reloc.CODE");
+    let warned = stderr.contains("relocation sections `reloc.CODE` were dropped");
+    assert!(kept != warned, "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn print_memory_config() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 2 16)
+    (global $__stack_pointer (mut i32) (i32.const 65536))
+    (global $__heap_base i32 (i32.const 70000))
+)
+"#,
+    );
+    let stderr = lld.link_ok(&["--print-memory-config", "--max-memory=1048576"]);
+    assert!(
+        stderr.contains("initial memory: 2 pages (131072 bytes)"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("maximum memory: 16 pages (1048576 bytes)"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("__stack_pointer: 0x10000"), "{stderr}");
+    assert!(stderr.contains("__heap_base: 0x11170"), "{stderr}");
+
+    // The maximum size of this 64-bit memory doesn't fit in a `u64` of bytes.
+    // Creating the component fails, but only after printing the config.
+    let lld = FakeLld::new(r#"(module (memory (export "memory") i64 1 0x1000000000000))"#);
+    let stderr = lld.link_err(&["--print-memory-config"]);
+    assert!(
+        stderr.contains("maximum memory: 281474976710656 pages (overflow)"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn print_adapter_kind() {
+    // Like a binary with `fn main`, which exports `_start`.
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (func (export "_start"))
+)
+"#,
+    );
+    let stderr = lld.link_ok(&["--print-adapter-kind"]);
+    assert!(stderr.contains("adapter kind: command"), "{stderr}");
+    assert_component(&lld.output());
+
+    // An explicit choice is printed as is.
+    let stderr = lld.link_ok(&["--print-adapter-kind", "--wasi-adapter=reactor"]);
+    assert!(stderr.contains("adapter kind: reactor"), "{stderr}");
+
+    // Like a `cdylib`, which doesn't.
+    let lld = FakeLld::new(REACTOR_WAT);
+    let stderr = lld.link_ok(&["--print-adapter-kind"]);
+    assert!(stderr.contains("adapter kind: reactor"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn target() {
+    // The adapter is selected by the triple, and the kind of adapter is still
+    // detected from the module.
+    let lld = FakeLld::new(REACTOR_WAT);
+    for target in ["wasm32-wasip1", "wasm32-wasip2"] {
+        let stderr = lld.link_ok(&[&format!("--target={target}"), "--print-adapter-kind"]);
+        assert!(stderr.contains("adapter kind: reactor"), "{stderr}");
+        assert!(core_module_sections(&lld.output()).len() > 1);
+    }
+
+    let lld = FakeLld::new(
+        r#"
+(module
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let stderr = lld.link_ok(&["--target=wasm32-unknown-unknown", "--print-adapter-kind"]);
+    assert!(stderr.contains("adapter kind: none"), "{stderr}");
+    assert_eq!(core_module_sections(&lld.output()).len(), 1);
+
+    let stderr = lld.link_err(&["--target=wasm32-wasip1-threads"]);
+    assert!(
+        stderr.contains("no bundled WASI adapter for `wasip1-threads`"),
+        "{stderr}"
+    );
+    let stderr = lld.link_err(&["--target=wasm64-wasip2"]);
+    assert!(
+        stderr.contains("no bundled 64-bit WASI adapter"),
+        "{stderr}"
+    );
+    let stderr = lld.link_err(&["--target=x86_64-unknown-linux-gnu"]);
+    assert!(stderr.contains("must be a `wasm32` target"), "{stderr}");
+
+    let lld = FakeLld::new(
+        r#"
+(module
+    (memory (export "memory") i64 1)
+)
+"#,
+    );
+    let stderr = lld.link_err(&["--target=wasm32-wasip2"]);
+    assert!(
+        stderr.contains("doesn't match `--target=wasm32-wasip2`"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn no_adapter_for() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--no-adapter-for=wasi_snapshot_preview1"]);
+    let component = lld.output();
+    assert_component(&component);
+    assert_eq!(core_module_sections(&component).len(), 1);
+    let imports = component_imports(&component);
+    assert_eq!(imports, ["wasi-snapshot-preview1"]);
+
+    let stderr = lld.link_err(&[
+        "--no-adapter-for=wasi_unstable",
+        "--preview1-aliases=wasi_unstable",
+    ]);
+    assert!(stderr.contains("conflicts with an adapter"), "{stderr}");
+}
+
+#[test]
+fn help_topics() {
+    let output = Command::new(component_ld())
+        .arg("--help=adapters")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("--wasi-adapter"), "{stdout}");
+    assert!(stdout.contains("--adapt "), "{stdout}");
+
+    let output = Command::new(component_ld())
+        .arg("--help=nope")
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("must be one of: adapters, wit, debugging-links"),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn smoke_test() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (data (i32.const 65535) "xy")
+)
+"#,
+    );
+    // The component is valid, it only fails when instantiated.
+    lld.link_ok(&[]);
+    let stderr = lld.link_err(&["--smoke-test"]);
+    assert!(
+        stderr.contains("component would fail to instantiate"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("data segment 0 of core module") && stderr.contains("out of bounds"),
+        "{stderr}"
+    );
+
+    let lld = FakeLld::new(REACTOR_WAT);
+    lld.link_ok(&["--smoke-test"]);
+}
+
+#[test]
+#[cfg(unix)]
+fn normalize_imports() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "wasi:io/poll@0.2.1" "f" (func))
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let wit = "package wasi:io@0.2.1; interface poll { f: func(); } world w { import poll; }";
+    let args = ["--wasi-adapter=none", "--component-type-inline", wit];
+
+    lld.link_ok(&[&args[..], &["--normalize-imports=wasi@0.2.0"]].concat());
+    let component = lld.output();
+    assert_component(&component);
+    let imports = component_imports(&component);
+    assert_eq!(imports, ["wasi:io/poll@0.2.0"]);
+
+    let stderr = lld.link_err(&[&args[..], &["--normalize-imports=wasi:io@0.3.0"]].concat());
+    assert!(stderr.contains("aren't semver-compatible"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn missing_world_exports() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (memory (export "memory") 1)
+    (func (export "present"))
+)
+"#,
+    );
+    let wit = "package test:pkg;
+        interface i { f: func(); }
+        world w { export present: func(); export absent: func(); export i; }";
+    let stderr = lld.link_err(&["--wasi-adapter=none", "--component-type-inline", wit]);
+    assert!(
+        stderr.contains(
+            "required by the exports of its component type world: `absent`, `test:pkg/i#f`"
+        ),
+        "{stderr}"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn large_component() {
+    const SIZE: usize = 4 << 20;
+    let lld = FakeLld::new(&format!(
+        r#"
+(module
+    (import "wasi_snapshot_preview1" "fd_write"
+        (func (param i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 128)
+    (data (i32.const 0) "{}")
+)
+"#,
+        "a".repeat(SIZE)
+    ));
+    lld.link_ok(&[]);
+    let component = lld.output();
+    assert!(component.len() > SIZE);
+    assert_component(&component);
+}
+
+#[test]
+#[cfg(unix)]
+fn realloc() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "test:pkg/i" "f" (func (param i32)))
+    (memory (export "memory") 1)
+    (func (export "my_realloc") (param i32 i32 i32 i32) (result i32)
+        i32.const 8)
+    (func (export "not_realloc") (param i32) (result i32)
+        local.get 0)
+)
+"#,
+    );
+    let wit = "package test:pkg; interface i { f: func() -> string; } world w { import i; }";
+    let args = ["--wasi-adapter=none", "--component-type-inline", wit];
+
+    // Returning a string from an import requires a `realloc` function.
+    let stderr = lld.link_err(&args);
+    assert!(
+        stderr.contains("module does not export a function named `cabi_realloc`"),
+        "{stderr}"
+    );
+
+    lld.link_ok(&[&args[..], &["--realloc=my_realloc"]].concat());
+    assert_component(&lld.output());
+
+    let stderr = lld.link_err(&[&args[..], &["--realloc=missing"]].concat());
+    assert!(
+        stderr.contains("realloc function `missing` isn't exported"),
+        "{stderr}"
+    );
+    let stderr = lld.link_err(&[&args[..], &["--realloc=not_realloc"]].concat());
+    assert!(stderr.contains("must have the signature"), "{stderr}");
+}
+
+#[test]
+#[cfg(unix)]
+fn require_component_type() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let stderr = lld.link_err(&["--require-component-type"]);
+    assert!(
+        stderr.contains("no component type information was found"),
+        "{stderr}"
+    );
+
+    lld.link_ok(&[
+        "--require-component-type",
+        "--component-type-inline",
+        "package test:pkg; world w { import f: func(); }",
+    ]);
+    assert_component(&lld.output());
+}
+
+#[test]
+#[cfg(unix)]
+fn object_manifest() {
+    let lld = FakeLld::new(REACTOR_WAT);
+    let manifest = lld.file("objects.txt");
+    std::fs::write(&manifest, "b.o\n-dash.o\n\n--also-dash.o\n").unwrap();
+    lld.link_ok(&[
+        "a.o",
+        &format!("--object-manifest={}", manifest.display()),
+        "--export=f",
+        "c.o",
+    ]);
+    let args = lld.args();
+    let objects = args
+        .iter()
+        .filter(|a| a.ends_with(".o"))
+        .map(|a| a.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        objects,
+        ["a.o", "b.o", "./-dash.o", "./--also-dash.o", "c.o"]
+    );
+    let export = args.iter().position(|a| a == "--export=f").unwrap();
+    let c = args.iter().position(|a| a == "c.o").unwrap();
+    assert!(export < c);
+}
+
+#[test]
+#[cfg(unix)]
+fn sign_key() {
+    use ed25519_dalek::Verifier;
+
+    let lld = FakeLld::new(REACTOR_WAT);
+    let secret = [7u8; 32];
+    let key = lld.file("key.hex");
+    let hex = secret
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    std::fs::write(&key, format!("{hex}\n")).unwrap();
+    lld.link_ok(&[&format!("--sign-key={}", key.display())]);
+
+    let output = lld.output();
+    let signature = std::fs::read(lld.file("out.wasm.sig")).unwrap();
+    let signature = ed25519_dalek::Signature::from_slice(&signature).unwrap();
+    let public = ed25519_dalek::SigningKey::from_bytes(&secret).verifying_key();
+    public.verify(&output, &signature).unwrap();
+    assert!(public.verify(b"something else", &signature).is_err());
+}
+
+#[test]
+#[cfg(unix)]
+fn compose_with() {
+    let lld = FakeLld::new(
+        r#"
+(module
+    (import "foo:app/api" "f" (func))
+    (func (export "foo:app/run#run") call 0)
+    (memory (export "memory") 1)
+)
+"#,
+    );
+    let wit = "
+        package foo:app;
+        interface api { f: func(); }
+        interface run { run: func(); }
+        world w {
+            import api;
+            export run;
+        }
+    ";
+    let dependency = lld.file("dep.wasm");
+    std::fs::write(
+        &dependency,
+        wat::parse_str(
+            r#"
+(component
+    (core module $m (func (export "f")))
+    (core instance $i (instantiate $m))
+    (func $f (canon lift (core func $i "f")))
+    (instance $api (export "f" (func $f)))
+    (export "foo:app/api" (instance $api))
+)
+"#,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let compose_with = format!("--compose-with={}", dependency.display());
+    let args = ["--wasi-adapter=none", "--component-type-inline", wit];
+
+    lld.link_ok(&[&args[..], &[&compose_with]].concat());
+    let component = lld.output();
+    assert_component(&component);
+    assert!(component_imports(&component).is_empty());
+
+    // A dependency which satisfies none of the imports is rejected.
+    std::fs::write(&dependency, wat::parse_str("(component)").unwrap()).unwrap();
+    let stderr = lld.link_err(&[&args[..], &[&compose_with]].concat());
+    assert!(
+        stderr.contains("none of the imports of the component are exported"),
+        "{stderr}"
+    );
+}